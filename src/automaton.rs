@@ -0,0 +1,738 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::grid::{Grid, MOORE};
+use crate::math::Vector2;
+
+/// A cellular automaton that advances a generation at a time.
+pub trait Automaton {
+    /// Advance one generation, returning whether any cell changed.
+    fn step(&mut self) -> bool;
+
+    /// The number of generations advanced so far.
+    fn generation(&self) -> usize;
+
+    /// Advance `generations` generations.
+    fn run(&mut self, generations: usize) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+
+    /// Advance until a generation changes nothing. Returns the number of generations that did
+    /// change something.
+    ///
+    /// **Note:** Never returns for automata that oscillate or grow forever. The detectors in
+    /// [`crate::cycle`] find out whether a sequence of generations settles, as day 11 does.
+    fn run_until_stable(&mut self) -> usize {
+        let mut changed = 0;
+
+        while self.step() {
+            changed += 1;
+        }
+
+        changed
+    }
+}
+
+/// The cells directly surrounding `at`, a neighbourhood for [`Dense`].
+pub fn moore<C: Copy>(grid: &Grid<C>, at: Vector2<isize>, out: &mut Vec<C>) {
+    out.extend(MOORE.iter().filter_map(|&dir| grid.get(at + dir).copied()));
+}
+
+/// The first cell in each of the eight directions from `at` that isn't `transparent`.
+///
+/// Looks along each [`Grid::ray`], so on a wrapping grid sight stops short of going round to
+/// `at` and past a repeating edge the edge cell is only looked at once.
+///
+/// Use this to build a neighbourhood for [`Dense`], e.g.
+/// `|grid, at, out| line_of_sight(grid, at, out, |c| c == Cell::Floor)`.
+pub fn line_of_sight<C: Copy>(
+    grid: &Grid<C>,
+    at: Vector2<isize>,
+    out: &mut Vec<C>,
+    transparent: impl Fn(C) -> bool,
+) {
+    out.extend(
+        MOORE
+            .iter()
+            .filter_map(|&dir| grid.ray(at, dir).copied().find(|&c| !transparent(c))),
+    );
+}
+
+/// Finds the neighbours of a cell on a [`Grid`] for a [`Dense`] automaton.
+///
+/// Implemented for closures, such as [`moore`] or ones calling [`line_of_sight`], which look
+/// the neighbours up every generation, and for a precomputed [`NeighbourGraph`].
+pub trait Neighbourhood<C> {
+    /// Push the neighbours of the cell at `at` onto `out`.
+    fn neighbours(&self, grid: &Grid<C>, at: Vector2<isize>, out: &mut Vec<C>);
+}
+
+impl<C, F> Neighbourhood<C> for F
+where
+    F: Fn(&Grid<C>, Vector2<isize>, &mut Vec<C>),
+{
+    fn neighbours(&self, grid: &Grid<C>, at: Vector2<isize>, out: &mut Vec<C>) {
+        self(grid, at, out)
+    }
+}
+
+/// Which cells a cell can see, looking in the eight directions of [`MOORE`].
+///
+/// Looking in a direction stops at the first cell that isn't transparent, or after
+/// `max_range` cells.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Visibility<C> {
+    max_range: Option<usize>,
+    transparent: Vec<C>,
+}
+
+impl<C: Copy + Eq> Visibility<C> {
+    /// Only the directly surrounding cells, equivalent to [`moore`].
+    pub fn adjacent() -> Self {
+        Self {
+            max_range: Some(1),
+            transparent: vec![],
+        }
+    }
+
+    /// Every cell up to the edge of the grid.
+    pub fn unlimited() -> Self {
+        Self {
+            max_range: None,
+            transparent: vec![],
+        }
+    }
+
+    pub fn max_range(mut self, max_range: usize) -> Self {
+        self.max_range = Some(max_range);
+
+        self
+    }
+
+    /// Look past cells of this type, e.g. floor between seats.
+    pub fn see_through(mut self, cell: C) -> Self {
+        self.transparent.push(cell);
+
+        self
+    }
+
+    /// Compute the neighbours of every cell in `grid`, looking along each [`Grid::ray`] so
+    /// sight follows the boundaries of `grid` like [`line_of_sight`] does.
+    pub fn graph(&self, grid: &Grid<C>) -> NeighbourGraph {
+        let mut offsets = Vec::with_capacity(grid.cells.len() + 1);
+        let mut neighbours = vec![];
+        let mut outside = vec![];
+        let max_range = self.max_range.unwrap_or(usize::MAX);
+
+        offsets.push(0);
+        for idx in 0..grid.cells.len() {
+            let at = grid.location(idx);
+
+            for &dir in MOORE.iter() {
+                let visible = grid
+                    .ray_locations(at, dir)
+                    .take(max_range)
+                    .find(|&l| grid.get(l).is_some_and(|c| !self.transparent.contains(c)));
+
+                if let Some(location) = visible {
+                    let resolved = grid.resolve(location).and_then(|l| grid.index(l));
+
+                    neighbours.push(resolved.unwrap_or_else(|| {
+                        outside.push(location);
+
+                        grid.cells.len() + outside.len() - 1
+                    }));
+                }
+            }
+            offsets.push(neighbours.len());
+        }
+
+        NeighbourGraph {
+            width: grid.width,
+            height: grid.height,
+            offsets,
+            neighbours,
+            outside,
+        }
+    }
+}
+
+/// The neighbours of every cell of a grid, computed once by [`Visibility::graph`].
+///
+/// The graph stays valid as long as cells don't change between transparent and not
+/// transparent, e.g. seats filling and emptying while floor stays floor.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NeighbourGraph {
+    width: usize,
+    height: usize,
+    /// The neighbours of cell `i` are `neighbours[offsets[i]..offsets[i + 1]]`.
+    offsets: Vec<usize>,
+    neighbours: Vec<usize>,
+    /// The locations of neighbours past an infinite edge, neighbour `cells + i` is `outside[i]`.
+    outside: Vec<Vector2<isize>>,
+}
+
+impl NeighbourGraph {
+    /// The indices, in row-major order, of the neighbours of the cell at index `idx`. Indices
+    /// past the last cell are neighbours past an infinite edge of the grid.
+    pub fn neighbours_of(&self, idx: usize) -> &[usize] {
+        &self.neighbours[self.offsets[idx]..self.offsets[idx + 1]]
+    }
+}
+
+impl<C: Copy> Neighbourhood<C> for NeighbourGraph {
+    /// **Note:** Panics if `grid` isn't the size of the grid the graph was computed for.
+    fn neighbours(&self, grid: &Grid<C>, at: Vector2<isize>, out: &mut Vec<C>) {
+        assert_eq!(
+            (grid.width, grid.height),
+            (self.width, self.height),
+            "Neighbour graph used with a grid of a different size"
+        );
+
+        if let Some(idx) = grid.index(at) {
+            out.extend(self.neighbours_of(idx).iter().map(|&n| {
+                match grid.cells.get(n) {
+                    Some(&cell) => cell,
+                    None => *grid
+                        .get(self.outside[n - grid.cells.len()])
+                        .expect("Cells past an infinite edge are always there"),
+                }
+            }));
+        }
+    }
+}
+
+/// An automaton on a bounded 2D grid.
+///
+/// The neighbourhood pushes the neighbours of a cell, given the whole grid and the cell's
+/// location, and the rule computes a cell's next state from its current state and neighbours.
+///
+/// Generations are written into a second buffer that's swapped with the grid afterwards, so
+/// stepping doesn't allocate once the neighbour buffer has grown.
+pub struct Dense<C, N, R> {
+    grid: Grid<C>,
+    next: Vec<C>,
+    neighbours: Vec<C>,
+    neighbourhood: N,
+    rule: R,
+    generation: usize,
+    changed: usize,
+}
+
+impl<C, N, R> Dense<C, N, R>
+where
+    C: Copy + Eq,
+    N: Neighbourhood<C>,
+    R: Fn(C, &[C]) -> C,
+{
+    pub fn new(grid: Grid<C>, neighbourhood: N, rule: R) -> Self {
+        Self {
+            next: grid.cells.clone(),
+            grid,
+            neighbours: vec![],
+            neighbourhood,
+            rule,
+            generation: 0,
+            changed: 0,
+        }
+    }
+
+    pub fn grid(&self) -> &Grid<C> {
+        &self.grid
+    }
+
+    pub fn into_grid(self) -> Grid<C> {
+        self.grid
+    }
+
+    /// The number of cells that changed in the last generation.
+    pub fn changed(&self) -> usize {
+        self.changed
+    }
+}
+
+impl<C, N, R> Automaton for Dense<C, N, R>
+where
+    C: Copy + Eq,
+    N: Neighbourhood<C>,
+    R: Fn(C, &[C]) -> C,
+{
+    fn step(&mut self) -> bool {
+        self.changed = 0;
+
+        for (idx, &cell) in self.grid.cells.iter().enumerate() {
+            let at = self.grid.location(idx);
+
+            self.neighbours.clear();
+            self.neighbourhood
+                .neighbours(&self.grid, at, &mut self.neighbours);
+
+            let next = (self.rule)(cell, &self.neighbours);
+            if next != cell {
+                self.changed += 1;
+            }
+            self.next[idx] = next;
+        }
+
+        std::mem::swap(&mut self.grid.cells, &mut self.next);
+        self.generation += 1;
+
+        self.changed > 0
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+}
+
+/// An automaton on an unbounded space, storing only the cells that aren't in the default state.
+///
+/// The neighbourhood pushes the locations neighbouring a location and the rule computes a cell's
+/// next state from its current state and neighbours. Only locations next to a stored cell are
+/// updated, so a default cell with only default neighbours must stay default.
+pub struct Sparse<P, C, N, R> {
+    cells: HashMap<P, C>,
+    neighbourhood: N,
+    rule: R,
+    generation: usize,
+}
+
+impl<P, C, N, R> Sparse<P, C, N, R>
+where
+    P: Copy + Hash + Eq,
+    C: Copy + Eq + Default,
+    N: Fn(P, &mut Vec<P>),
+    R: Fn(C, &[C]) -> C,
+{
+    pub fn new(cells: impl IntoIterator<Item = (P, C)>, neighbourhood: N, rule: R) -> Self {
+        Self {
+            cells: cells
+                .into_iter()
+                .filter(|(_, c)| *c != C::default())
+                .collect(),
+            neighbourhood,
+            rule,
+            generation: 0,
+        }
+    }
+
+    /// The cells that aren't in the default state.
+    pub fn cells(&self) -> &HashMap<P, C> {
+        &self.cells
+    }
+
+    pub fn get(&self, at: P) -> C {
+        self.cells.get(&at).copied().unwrap_or_default()
+    }
+}
+
+impl<P, C, N, R> Automaton for Sparse<P, C, N, R>
+where
+    P: Copy + Hash + Eq,
+    C: Copy + Eq + Default,
+    N: Fn(P, &mut Vec<P>),
+    R: Fn(C, &[C]) -> C,
+{
+    fn step(&mut self) -> bool {
+        let mut locations = vec![];
+        let mut candidates: HashSet<P> = HashSet::new();
+
+        for &location in self.cells.keys() {
+            candidates.insert(location);
+
+            locations.clear();
+            (self.neighbourhood)(location, &mut locations);
+            candidates.extend(locations.iter().copied());
+        }
+
+        let mut neighbours = vec![];
+        let next: HashMap<P, C> = candidates
+            .into_iter()
+            .filter_map(|location| {
+                locations.clear();
+                (self.neighbourhood)(location, &mut locations);
+
+                neighbours.clear();
+                neighbours.extend(locations.iter().map(|l| self.get(*l)));
+
+                let cell = (self.rule)(self.get(location), &neighbours);
+
+                if cell != C::default() {
+                    Some((location, cell))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let changed = next != self.cells;
+        self.cells = next;
+        self.generation += 1;
+
+        changed
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+}
+
+/// An automaton with two states on an unbounded space, storing only the live cells.
+///
+/// Each generation every live cell adds one to the neighbour count of each location the
+/// neighbourhood pushes for it, i.e. the locations that see it as a neighbour, pushed once for
+/// every time they see it. The rule then computes whether a cell lives from whether it's alive
+/// and its count. Only locations with a live neighbour or that are alive are updated, so a dead
+/// cell without live neighbours must stay dead.
+pub struct SparseSet<P, N, R> {
+    live: HashSet<P>,
+    next: HashSet<P>,
+    counts: HashMap<P, usize>,
+    neighbours: Vec<P>,
+    neighbourhood: N,
+    rule: R,
+    generation: usize,
+}
+
+impl<P, N, R> SparseSet<P, N, R>
+where
+    P: Copy + Hash + Eq,
+    N: Fn(P, &mut Vec<P>),
+    R: Fn(bool, usize) -> bool,
+{
+    pub fn new(live: impl IntoIterator<Item = P>, neighbourhood: N, rule: R) -> Self {
+        Self {
+            live: live.into_iter().collect(),
+            next: HashSet::new(),
+            counts: HashMap::new(),
+            neighbours: vec![],
+            neighbourhood,
+            rule,
+            generation: 0,
+        }
+    }
+
+    pub fn live(&self) -> &HashSet<P> {
+        &self.live
+    }
+
+    pub fn is_alive(&self, at: P) -> bool {
+        self.live.contains(&at)
+    }
+}
+
+impl<P, N, R> Automaton for SparseSet<P, N, R>
+where
+    P: Copy + Hash + Eq,
+    N: Fn(P, &mut Vec<P>),
+    R: Fn(bool, usize) -> bool,
+{
+    fn step(&mut self) -> bool {
+        self.counts.clear();
+        for &cell in &self.live {
+            self.neighbours.clear();
+            (self.neighbourhood)(cell, &mut self.neighbours);
+
+            for &neighbour in &self.neighbours {
+                *self.counts.entry(neighbour).or_insert(0) += 1;
+            }
+        }
+
+        self.next.clear();
+        for (&location, &count) in &self.counts {
+            if (self.rule)(self.live.contains(&location), count) {
+                self.next.insert(location);
+            }
+        }
+        // Live cells without live neighbours don't show up in the counts
+        for &cell in &self.live {
+            if !self.counts.contains_key(&cell) && (self.rule)(true, 0) {
+                self.next.insert(cell);
+            }
+        }
+
+        let changed = self.next != self.live;
+        std::mem::swap(&mut self.live, &mut self.next);
+        self.generation += 1;
+
+        changed
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Boundary;
+
+    fn life(alive: bool, neighbours: &[bool]) -> bool {
+        let alive_neighbours = neighbours.iter().filter(|&&n| n).count();
+
+        alive_neighbours == 3 || (alive && alive_neighbours == 2)
+    }
+
+    fn moore_locations(at: Vector2<isize>, out: &mut Vec<Vector2<isize>>) {
+        out.extend(MOORE.iter().map(|&dir| at + dir));
+    }
+
+    #[test]
+    fn test_dense_blinker() {
+        let horizontal = vec![
+            vec![false, false, false],
+            vec![true, true, true],
+            vec![false, false, false],
+        ];
+        let horizontal = Grid::from_rows(horizontal).unwrap();
+        let mut automaton = Dense::new(horizontal.clone(), moore, life);
+
+        assert!(automaton.step());
+        assert_eq!(automaton.changed(), 4);
+        assert_eq!(
+            automaton.grid().rows().collect::<Vec<_>>(),
+            vec![
+                [false, true, false],
+                [false, true, false],
+                [false, true, false]
+            ]
+        );
+
+        automaton.run(3);
+        assert_eq!(automaton.generation(), 4);
+        assert_eq!(automaton.grid(), &horizontal);
+    }
+
+    #[test]
+    fn test_dense_until_stable() {
+        // A block is stable from the start while a lone cell dies after one generation
+        let cells = vec![
+            vec![true, true, false, false, false],
+            vec![true, true, false, false, true],
+        ];
+        let mut automaton = Dense::new(Grid::from_rows(cells).unwrap(), moore, life);
+
+        assert_eq!(automaton.run_until_stable(), 1);
+        assert_eq!(automaton.changed(), 0);
+        assert_eq!(automaton.grid().iter().filter(|&&c| c).count(), 4);
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let grid =
+            Grid::from_rows(vec![vec![1, 0, 0, 2], vec![0, 0, 0, 0], vec![3, 0, 0, 0]]).unwrap();
+        let mut seen = vec![];
+
+        line_of_sight(&grid, Vector2::new(0, 0), &mut seen, |c| c == 0);
+        assert_eq!(seen, vec![3, 2]);
+    }
+
+    #[test]
+    fn test_line_of_sight_wrap() {
+        let grid = Grid::from_rows(vec![vec![1, 0, 0], vec![0, 0, 0], vec![0, 0, 0]])
+            .unwrap()
+            .with_boundaries(Boundary::Wrap, Boundary::Wrap);
+        let mut seen = vec![];
+
+        // Going round would end up back at the cell itself
+        line_of_sight(&grid, Vector2::new(0, 0), &mut seen, |c| c == 0);
+        assert_eq!(seen, vec![]);
+
+        // Both up-left and, wrapping round, down-right
+        line_of_sight(&grid, Vector2::new(1, 1), &mut seen, |c| c == 0);
+        assert_eq!(seen, vec![1, 1]);
+
+        // Wrapping sideways down a tall grid passes every row before the ray comes back round
+        let mut cells = vec![vec![0; 3]; 6];
+        cells[5][2] = 1;
+        let grid = Grid::from_rows(cells)
+            .unwrap()
+            .with_boundaries(Boundary::Wrap, Boundary::Bounded);
+        assert_eq!(grid.ray(Vector2::new(0, 0), Vector2::new(1, 1)).count(), 5);
+
+        let mut seen = vec![];
+        line_of_sight(&grid, Vector2::new(0, 0), &mut seen, |c| c == 0);
+        assert_eq!(seen, vec![1]);
+
+        let grid = Grid::from_rows(vec![vec![0; 3]; 6])
+            .unwrap()
+            .with_boundaries(Boundary::Wrap, Boundary::Wrap);
+        assert_eq!(
+            grid.ray_locations(Vector2::new(0, 0), Vector2::new(1, 1))
+                .last(),
+            Some(Vector2::new(5, 5))
+        );
+    }
+
+    #[test]
+    fn test_line_of_sight_repeat() {
+        let grid = Grid::from_rows(vec![vec![1, 0], vec![0, 0]])
+            .unwrap()
+            .with_boundaries(Boundary::Repeat, Boundary::Repeat);
+        let mut seen = vec![];
+
+        // Past the edges the location stops moving rather than seeing the edge cell again
+        line_of_sight(&grid, Vector2::new(0, 0), &mut seen, |c| c == 0);
+        assert_eq!(seen, vec![]);
+
+        line_of_sight(&grid, Vector2::new(1, 1), &mut seen, |c| c == 0);
+        assert_eq!(seen, vec![1]);
+
+        let grid = Grid::from_rows(vec![vec![0, 0, 2]])
+            .unwrap()
+            .with_boundaries(Boundary::Repeat, Boundary::Bounded);
+        line_of_sight(&grid, Vector2::new(0, 0), &mut seen, |c| c == 0);
+        assert_eq!(seen, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_neighbour_graph() {
+        let grid =
+            Grid::from_rows(vec![vec![1, 0, 0, 2], vec![0, 0, 0, 0], vec![3, 0, 0, 4]]).unwrap();
+
+        let adjacent = Visibility::adjacent().graph(&grid);
+        assert_eq!(adjacent.neighbours_of(0), &[4, 5, 1]);
+
+        let visible = Visibility::unlimited().see_through(0).graph(&grid);
+        assert_eq!(visible.neighbours_of(0), &[8, 3]);
+
+        let short = Visibility::unlimited()
+            .max_range(2)
+            .see_through(0)
+            .graph(&grid);
+        assert_eq!(short.neighbours_of(0), &[8]);
+
+        let mut seen = vec![];
+        visible.neighbours(&grid, Vector2::new(3, 2), &mut seen);
+        assert_eq!(seen, vec![3, 2]);
+    }
+
+    #[test]
+    fn test_toroidal_glider() {
+        let mut cells = vec![vec![false; 5]; 5];
+        for &(x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)].iter() {
+            cells[y][x] = true;
+        }
+        let grid = Grid::from_rows(cells)
+            .unwrap()
+            .with_boundaries(Boundary::Wrap, Boundary::Wrap);
+        let mut automaton = Dense::new(grid.clone(), moore, life);
+
+        // A glider moves a cell diagonally every four generations, so it crosses the torus and
+        // lands back where it started
+        automaton.run(20);
+        assert_eq!(automaton.grid(), &grid);
+        assert_eq!(automaton.grid().iter().filter(|&&c| c).count(), 5);
+    }
+
+    #[test]
+    fn test_graph_matches_moore() {
+        let cells = vec![
+            vec![false, true, true, false],
+            vec![true, true, false, false],
+            vec![false, true, false, true],
+        ];
+        let grid = Grid::from_rows(cells).unwrap();
+
+        let mut dynamic = Dense::new(grid.clone(), moore, life);
+        let mut graph = Dense::new(grid.clone(), Visibility::adjacent().graph(&grid), life);
+
+        for _ in 0..5 {
+            assert_eq!(dynamic.step(), graph.step());
+            assert_eq!(dynamic.grid(), graph.grid());
+        }
+    }
+
+    #[test]
+    fn test_graph_follows_boundaries() {
+        let cells = vec![
+            vec![true, false, false, false],
+            vec![false, true, false, false],
+            vec![false, false, false, true],
+        ];
+        let torus = Grid::from_rows(cells.clone())
+            .unwrap()
+            .with_boundaries(Boundary::Wrap, Boundary::Wrap);
+
+        let adjacent = Visibility::adjacent().graph(&torus);
+        assert_eq!(adjacent.neighbours_of(0), &[3, 7, 4, 5, 1, 9, 8, 11]);
+
+        let mut dynamic = Dense::new(torus.clone(), moore, life);
+        let mut graph = Dense::new(torus, adjacent, life);
+        for _ in 0..5 {
+            assert_eq!(dynamic.step(), graph.step());
+            assert_eq!(dynamic.grid(), graph.grid());
+        }
+
+        // Neighbours past an infinite edge are looked up rather than stored
+        let infinite = Grid::from_rows(cells)
+            .unwrap()
+            .with_boundaries(Boundary::Infinite(true), Boundary::Bounded);
+        let (mut seen, mut expected) = (vec![], vec![]);
+        Visibility::adjacent().graph(&infinite).neighbours(
+            &infinite,
+            Vector2::new(0, 1),
+            &mut seen,
+        );
+        moore(&infinite, Vector2::new(0, 1), &mut expected);
+        assert_eq!(seen, expected);
+        assert_eq!(seen.iter().filter(|&&c| c).count(), 5);
+    }
+
+    #[test]
+    fn test_sparse_glider() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]
+            .iter()
+            .map(|&(x, y)| (Vector2::new(x, y), true))
+            .collect::<Vec<_>>();
+        let mut automaton = Sparse::new(glider.clone(), moore_locations, life);
+
+        automaton.run(4);
+
+        // After four generations a glider has moved one cell diagonally
+        let moved: HashMap<_, _> = glider
+            .into_iter()
+            .map(|(l, c)| (l + Vector2::new(1, 1), c))
+            .collect();
+        assert_eq!(automaton.cells(), &moved);
+        assert!(automaton.get(Vector2::new(2, 3)));
+        assert!(!automaton.get(Vector2::new(0, 0)));
+    }
+
+    #[test]
+    fn test_sparse_set_matches_sparse() {
+        let cells = [
+            (1, 0),
+            (2, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+            (6, 6),
+            (6, 7),
+            (7, 6),
+        ];
+        let mut sparse = Sparse::new(
+            cells.iter().map(|&(x, y)| (Vector2::new(x, y), true)),
+            moore_locations,
+            life,
+        );
+        let mut set = SparseSet::new(
+            cells.iter().map(|&(x, y)| Vector2::new(x, y)),
+            moore_locations,
+            |alive, count| count == 3 || (alive && count == 2),
+        );
+
+        for _ in 0..10 {
+            assert_eq!(sparse.step(), set.step());
+
+            let mut live: Vec<_> = sparse.cells().keys().map(|l| (l.x(), l.y())).collect();
+            let mut set_live: Vec<_> = set.live().iter().map(|l| (l.x(), l.y())).collect();
+            live.sort_unstable();
+            set_live.sort_unstable();
+            assert_eq!(live, set_live);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::env;
+use std::process;
+
+use advent_of_rust_2020::console::analysis::Analysis;
+use advent_of_rust_2020::console::cfg::ControlFlowGraph;
+use advent_of_rust_2020::console::Instruction;
+use advent_of_rust_2020::load_file;
+use advent_of_rust_2020::parser_combinators::parse_all_lines;
+
+fn main() {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let dot = args.iter().any(|arg| arg == "--dot");
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .unwrap_or_else(|| {
+            eprintln!("Usage: console_analyzer [--dot] <program>");
+            process::exit(1);
+        });
+
+    let input = load_file(path);
+    let (instructions, diagnostics) = parse_all_lines(&input, |line: &str| {
+        line.parse::<Instruction>()
+            .map(|instruction| ("", instruction))
+    });
+
+    if !diagnostics.is_empty() {
+        for diagnostic in diagnostics {
+            eprintln!("{}: {}", path, diagnostic);
+        }
+        process::exit(1);
+    }
+
+    let analysis = Analysis::new(&ControlFlowGraph::new(&instructions));
+
+    if dot {
+        print!("{}", analysis.to_dot());
+    } else {
+        print!("{}", analysis);
+    }
+}
@@ -0,0 +1,76 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use advent_of_rust_2020::console::debugger::{Command, Debugger};
+use advent_of_rust_2020::console::{Instruction, VM};
+use advent_of_rust_2020::load_file;
+use advent_of_rust_2020::parser_combinators::parse_all_lines;
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| {
+        eprintln!("Usage: console_debugger <program>");
+        process::exit(1);
+    });
+
+    let input = load_file(&path);
+    let (instructions, diagnostics) = parse_all_lines(&input, |line: &str| {
+        line.parse::<Instruction>()
+            .map(|instruction| ("", instruction))
+    });
+
+    if !diagnostics.is_empty() {
+        for diagnostic in diagnostics {
+            eprintln!("{}: {}", path, diagnostic);
+        }
+        process::exit(1);
+    }
+
+    let mut debugger = Debugger::new(VM::new(instructions));
+    let stdin = io::stdin();
+    let mut last_command = None;
+
+    println!(
+        "Loaded {} instructions, type `help` for help",
+        debugger.vm().program().len()
+    );
+    println!("{}", debugger.execute(Command::List(None)));
+
+    loop {
+        print!("(debug) ");
+        io::stdout().flush().expect("Failed to flush stdout");
+
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .expect("Failed to read stdin")
+            == 0
+        {
+            break;
+        }
+
+        let line = line.trim();
+        if line == "quit" || line == "q" {
+            break;
+        }
+
+        // An empty line repeats the previous command
+        let command = if line.is_empty() {
+            match &last_command {
+                Some(command) => Ok(Command::clone(command)),
+                None => continue,
+            }
+        } else {
+            line.parse::<Command>()
+        };
+
+        match command {
+            Ok(command) => {
+                println!("{}", debugger.execute(command.clone()));
+                last_command = Some(command);
+            }
+            Err(e) => println!("{}", e),
+        }
+    }
+}
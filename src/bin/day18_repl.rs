@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use advent_of_rust_2020::day18::Precedence;
+
+const PROMPT: &str = "> ";
+const HELP: &str = "Enter an expression to evaluate it, e.g. `1 + 2 * 3`.
+
+Commands:
+  :mode all       Evaluate under every precedence mode (default)
+  :mode equal     `+` and `*` have equal precedence
+  :mode addition  `+` is evaluated before `*`
+  :help           Show this help
+  :quit           Exit";
+
+fn evaluate(line: &str, modes: &[Precedence]) {
+    let env = HashMap::new();
+
+    for &mode in modes {
+        match mode.parse(line) {
+            Ok(expr) => match expr.evaluate(&env) {
+                Ok(value) => println!("{:>8}: {} = {}", mode, expr, value),
+                Err(e) => println!("{:>8}: {} failed: {}", mode, expr, e),
+            },
+            Err(e) => {
+                // Every mode shares the same syntax so a parse error is reported once
+                let column = PROMPT.chars().count() + e.column(line);
+                println!("{:>1$}^ {2}", "", column, e.message);
+
+                break;
+            }
+        }
+    }
+}
+
+fn main() {
+    let stdin = io::stdin();
+    let mut modes = Precedence::ALL.to_vec();
+
+    println!("Type `:help` for help");
+
+    loop {
+        print!("{}", PROMPT);
+        io::stdout().flush().expect("Failed to flush stdout");
+
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .expect("Failed to read stdin")
+            == 0
+        {
+            break;
+        }
+
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut command = line.split_whitespace();
+        match (command.next(), command.next()) {
+            (Some(":quit"), _) | (Some(":q"), _) => break,
+            (Some(":help"), _) => println!("{}", HELP),
+            (Some(":mode"), Some("all")) => modes = Precedence::ALL.to_vec(),
+            (Some(":mode"), Some(mode)) => match mode.parse::<Precedence>() {
+                Ok(mode) => modes = vec![mode],
+                Err(e) => println!("{}", e),
+            },
+            (Some(c), _) if c.starts_with(':') => println!("Unknown command `{}`", line),
+            _ => evaluate(line, &modes),
+        }
+    }
+}
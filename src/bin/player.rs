@@ -0,0 +1,190 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Read};
+use std::panic;
+use std::process::{self, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+
+use advent_of_rust_2020::playback::{self, Key, Simulation};
+use advent_of_rust_2020::{day03, day11, day12, day17, load_file};
+
+const USAGE: &str = "Usage: player <simulation> <input> [options]
+
+Simulations:
+  seats [rules]          Day 11 seating, rules default to `part1`, e.g. `part2 vacate=4`
+  cubes [dimensions]     Day 17 Conway cubes in 2 to 6 dimensions, default 3
+  ship [waypoint]        Day 12 ship, steering by waypoint when `waypoint` is given
+  toboggan [right,down]  Day 03 toboggan, slope default 3,1, right can be negative
+
+Options:
+  --fps <n>              Frames per second, default 4
+  --cycles <n>           Cycles of cubes to simulate, default 6
+  --max-frames <n>       Stop after n frames
+  --frames-to <file>     Write every frame to file instead of playing them";
+
+struct Options {
+    simulation: String,
+    input: String,
+    argument: Option<String>,
+    fps: f64,
+    cycles: usize,
+    max_frames: Option<usize>,
+    frames_to: Option<String>,
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("Expected a value for `{}`", flag))?;
+
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value `{}` for `{}`", value, flag))
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = env::args().skip(1);
+    let mut positional = vec![];
+    let mut options = Options {
+        simulation: String::new(),
+        input: String::new(),
+        argument: None,
+        fps: 4.0,
+        cycles: 6,
+        max_frames: None,
+        frames_to: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fps" => options.fps = parse_value(&arg, args.next())?,
+            "--cycles" => options.cycles = parse_value(&arg, args.next())?,
+            "--max-frames" => options.max_frames = Some(parse_value(&arg, args.next())?),
+            "--frames-to" => options.frames_to = Some(parse_value(&arg, args.next())?),
+            flag if flag.starts_with("--") => return Err(format!("Unknown option `{}`", flag)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    options.simulation = positional.next().ok_or("Expected a simulation")?;
+    options.input = positional.next().ok_or("Expected an input file")?;
+    options.argument = positional.next();
+
+    Ok(options)
+}
+
+fn simulation(options: &Options) -> Result<Box<dyn Simulation>, String> {
+    let input = load_file(&options.input);
+    let argument = options.argument.as_deref();
+
+    match options.simulation.as_str() {
+        "seats" => Ok(Box::new(day11::seating_simulation(
+            &input,
+            argument.unwrap_or("part1"),
+        )?)),
+        "cubes" => {
+            let dimensions =
+                argument.map_or(Ok(3), |d| parse_value("dimensions", Some(d.into())))?;
+
+            day17::cube_simulation(&input, dimensions, options.cycles)
+        }
+        "ship" => Ok(Box::new(day12::ship_simulation(
+            &input,
+            argument == Some("waypoint"),
+        )?)),
+        "toboggan" => Ok(Box::new(day03::toboggan_simulation(
+            &input,
+            argument.unwrap_or("3,1").parse()?,
+        )?)),
+        other => Err(format!("Unknown simulation `{}`", other)),
+    }
+}
+
+/// Switch the terminal to reading single key presses without echoing them. Returns whether it
+/// worked, without it keys only arrive after enter is pressed.
+///
+/// Ctrl-C arrives as a key press rather than killing the player, so the terminal is always
+/// switched back.
+fn set_raw_terminal(raw: bool) -> bool {
+    let settings: &[&str] = if raw {
+        &["-icanon", "-echo", "-isig", "min", "1"]
+    } else {
+        &["icanon", "echo", "isig"]
+    };
+
+    Command::new("stty")
+        .args(settings)
+        .stdin(Stdio::inherit())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Keeps the terminal raw until dropped, or until the player panics.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enable() -> Option<Self> {
+        if !set_raw_terminal(true) {
+            return None;
+        }
+
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            set_raw_terminal(false);
+            previous(info);
+        }));
+
+        Some(RawTerminal)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        set_raw_terminal(false);
+    }
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(1);
+    });
+    let mut simulation = simulation(&options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    if let Some(path) = &options.frames_to {
+        let mut file = BufWriter::new(File::create(path).expect("Unable to create file"));
+        let frames = playback::write_frames(simulation.as_mut(), options.max_frames, &mut file)
+            .expect("Unable to write frames");
+
+        println!("Wrote {} frames to {}", frames, path);
+        return;
+    }
+
+    let raw_terminal = RawTerminal::enable();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            let key = byte.ok().and_then(|b| Key::from_char(b as char));
+
+            if let Some(key) = key {
+                if sender.send(key).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let result = playback::play(
+        simulation.as_mut(),
+        options.fps,
+        options.max_frames,
+        &receiver,
+        &mut io::stdout(),
+    );
+
+    drop(raw_terminal);
+    result.expect("Unable to write to stdout");
+}
@@ -0,0 +1,668 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+pub mod analysis;
+pub mod cfg;
+pub mod debugger;
+pub mod isa;
+
+use isa::{Flow, InstructionSet, Op};
+
+/// An instruction of the handheld game console, see [`InstructionSet::handheld`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Instruction {
+    Nop(isize),
+    Acc(isize),
+    Jmp(isize),
+}
+
+impl Instruction {
+    pub fn is_nop(&self) -> bool {
+        matches!(self, Instruction::Nop(_))
+    }
+
+    pub fn is_jmp(&self) -> bool {
+        matches!(self, Instruction::Jmp(_))
+    }
+
+    /// The instruction with `nop` and `jmp` swapped, `acc` can't be flipped.
+    pub fn flipped(&self) -> Option<Instruction> {
+        match *self {
+            Instruction::Nop(v) => Some(Instruction::Jmp(v)),
+            Instruction::Jmp(v) => Some(Instruction::Nop(v)),
+            Instruction::Acc(_) => None,
+        }
+    }
+
+    /// The offset from this instruction to the next one executed.
+    pub fn ip_delta(&self) -> isize {
+        match *self {
+            Instruction::Jmp(offset) => offset,
+            Instruction::Acc(_) | Instruction::Nop(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Nop(v) => write!(f, "nop {:+}", v),
+            Instruction::Acc(v) => write!(f, "acc {:+}", v),
+            Instruction::Jmp(v) => write!(f, "jmp {:+}", v),
+        }
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+
+        match (parts.next(), parts.next()) {
+            (Some(i), Some(value)) => {
+                let parsed_value = value
+                    .parse::<isize>()
+                    .map_err(|e| format!("Failed to parse instruction `{}`. {}", s, e));
+
+                match i {
+                    "nop" => parsed_value.map(Instruction::Nop),
+                    "acc" => parsed_value.map(Instruction::Acc),
+                    "jmp" => parsed_value.map(Instruction::Jmp),
+                    _ => Err(format!("Invalid instruction `{}`", s)),
+                }
+            }
+            _ => Err(format!("Invalid instruction `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HaltStatus {
+    /// Ran off the end of the program, i.e. terminated normally.
+    Done,
+    /// Was about to execute an instruction for the second time.
+    InfiniteLoop,
+    /// Executed the maximum number of steps allowed by the [`HaltPolicy`].
+    StepLimit,
+    /// Jumped to an address outside of the program.
+    OutOfBounds(isize),
+    /// Was about to execute an instruction at this address that the instruction set can't
+    /// execute, e.g. a hand built [`Op`] with the wrong operands.
+    InvalidInstruction(usize),
+}
+
+/// Decides when a [`VM`] halts, other than by running off the end of the program.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct HaltPolicy {
+    /// Halt with [`HaltStatus::InfiniteLoop`] instead of executing an instruction twice.
+    pub detect_loops: bool,
+    /// Halt with [`HaltStatus::StepLimit`] after executing this many instructions in total.
+    pub max_steps: Option<usize>,
+    /// Treat jumping beyond the end of the program as [`HaltStatus::Done`] rather than
+    /// [`HaltStatus::OutOfBounds`]. Jumping before the start is always out of bounds.
+    pub allow_jump_past_end: bool,
+}
+
+impl HaltPolicy {
+    /// Whether a machine about to execute `ip` of a program of length `len` should halt.
+    /// `executed` is the number of times `ip` has already been executed.
+    fn check(&self, ip: isize, len: usize, steps: usize, executed: usize) -> Option<HaltStatus> {
+        let len = len as isize;
+
+        if ip < 0 || (ip > len && !self.allow_jump_past_end) {
+            Some(HaltStatus::OutOfBounds(ip))
+        } else if ip >= len {
+            Some(HaltStatus::Done)
+        } else if self.max_steps.is_some_and(|max| steps >= max) {
+            Some(HaltStatus::StepLimit)
+        } else if self.detect_loops && executed > 0 {
+            Some(HaltStatus::InfiniteLoop)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for HaltPolicy {
+    fn default() -> Self {
+        Self {
+            detect_loops: true,
+            max_steps: None,
+            allow_jump_past_end: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Event {
+    Executed { ip: usize, op: Op, acc_delta: isize },
+    Halted(HaltStatus),
+}
+
+/// An executed instruction recorded by a [`VM`] with tracing enabled.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TraceEntry {
+    pub ip: usize,
+    pub op: Op,
+    pub acc_delta: isize,
+    /// The registers before the instruction was executed.
+    pub registers: Vec<isize>,
+}
+
+/// A cycle in the executed instructions, found by [`VM::cycle`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cycle {
+    /// The step at which the cycle was first entered.
+    pub start: usize,
+    /// The first instruction of the cycle.
+    pub entry: usize,
+    /// The instructions executed in one iteration of the cycle, starting at `entry`.
+    pub body: Vec<TraceEntry>,
+}
+
+impl Cycle {
+    /// The change to `acc` of one iteration of the cycle.
+    pub fn acc_delta(&self) -> isize {
+        self.body.iter().map(|entry| entry.acc_delta).sum()
+    }
+
+    /// The cycle and its instructions, written in the assembly of `isa`.
+    pub fn display<'a>(&'a self, isa: &'a InstructionSet) -> impl fmt::Display + 'a {
+        CycleDisplay { cycle: self, isa }
+    }
+}
+
+struct CycleDisplay<'a> {
+    cycle: &'a Cycle,
+    isa: &'a InstructionSet,
+}
+
+impl fmt::Display for CycleDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Cycle entered at ip {} after {} steps, {} instructions long, acc {:+} per iteration",
+            self.cycle.entry,
+            self.cycle.start,
+            self.cycle.body.len(),
+            self.cycle.acc_delta()
+        )?;
+
+        for entry in &self.cycle.body {
+            writeln!(
+                f,
+                "{:>6}: {:<10} acc {:+}",
+                entry.ip,
+                self.isa.format_op(&entry.op),
+                entry.acc_delta
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs programs of an [`InstructionSet`], the handheld game console's unless created with
+/// [`VM::with_isa`].
+///
+/// `acc` is the first register of the instruction set.
+pub struct VM {
+    isa: InstructionSet,
+    program: Vec<Op>,
+    registers: Vec<isize>,
+    ip: isize,
+    steps: usize,
+    execution_counts: Vec<usize>,
+    trace: Option<Vec<TraceEntry>>,
+    policy: HaltPolicy,
+    halt_status: Option<HaltStatus>,
+}
+
+impl VM {
+    pub fn new(instructions: Vec<Instruction>) -> Self {
+        Self::with_policy(instructions, HaltPolicy::default())
+    }
+
+    pub fn with_policy(instructions: Vec<Instruction>, policy: HaltPolicy) -> Self {
+        let program = instructions.into_iter().map(Op::from).collect();
+
+        Self::with_isa(InstructionSet::handheld(), program, policy)
+    }
+
+    /// Run `program` of another instruction set. With several registers and conditional jumps
+    /// executing an instruction twice doesn't mean the program is stuck, so `policy` usually
+    /// shouldn't detect loops.
+    pub fn with_isa(isa: InstructionSet, program: Vec<Op>, policy: HaltPolicy) -> Self {
+        Self {
+            registers: vec![0; isa.registers().len()],
+            isa,
+            execution_counts: vec![0; program.len()],
+            program,
+            ip: 0,
+            steps: 0,
+            trace: None,
+            policy,
+            halt_status: None,
+        }
+    }
+
+    pub fn acc(&self) -> isize {
+        self.registers.first().copied().unwrap_or(0)
+    }
+
+    pub fn registers(&self) -> &[isize] {
+        &self.registers
+    }
+
+    pub fn register(&self, name: &str) -> Option<isize> {
+        self.isa.register(name).map(|r| self.registers[r])
+    }
+
+    pub fn set_register(&mut self, name: &str, value: isize) -> Option<()> {
+        let r = self.isa.register(name)?;
+        self.registers[r] = value;
+
+        Some(())
+    }
+
+    pub fn ip(&self) -> isize {
+        self.ip
+    }
+
+    /// The number of instructions executed so far.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn isa(&self) -> &InstructionSet {
+        &self.isa
+    }
+
+    pub fn program(&self) -> &[Op] {
+        &self.program
+    }
+
+    pub fn policy(&self) -> HaltPolicy {
+        self.policy
+    }
+
+    /// Replace the instruction at `ip`, returning the previous one. A halted VM can be resumed
+    /// after patching.
+    ///
+    /// Loop detection starts over, as the patch can change which instructions run again, so a
+    /// VM halted by [`HaltStatus::InfiniteLoop`] doesn't halt again straight away.
+    pub fn patch(&mut self, ip: usize, op: impl Into<Op>) -> Option<Op> {
+        let previous = self.program.get_mut(ip)?;
+        self.halt_status = None;
+        self.execution_counts
+            .iter_mut()
+            .for_each(|count| *count = 0);
+
+        Some(std::mem::replace(previous, op.into()))
+    }
+
+    /// Why the VM halted, if it has.
+    pub fn halt_status(&self) -> Option<HaltStatus> {
+        self.halt_status
+    }
+
+    /// Start recording every executed instruction, which enables [`VM::step_back`],
+    /// [`VM::rewind_to`] and [`VM::cycle`]. Only steps executed after this are recorded.
+    pub fn enable_trace(&mut self) {
+        if self.trace.is_none() {
+            self.trace = Some(vec![]);
+        }
+    }
+
+    /// The recorded instructions, if tracing is enabled. The entry at index `n` is the
+    /// instruction executed by step `n` counting from when tracing was enabled.
+    pub fn trace(&self) -> Option<&[TraceEntry]> {
+        self.trace.as_deref()
+    }
+
+    /// Undo the most recently executed instruction. Returns `None` when there's no recorded
+    /// history to undo.
+    pub fn step_back(&mut self) -> Option<TraceEntry> {
+        let entry = self.trace.as_mut()?.pop()?;
+
+        self.ip = entry.ip as isize;
+        self.registers.clone_from(&entry.registers);
+        self.steps -= 1;
+        // Counts are reset by patching, so history from before a patch may not be counted
+        self.execution_counts[entry.ip] = self.execution_counts[entry.ip].saturating_sub(1);
+        self.halt_status = None;
+
+        Some(entry)
+    }
+
+    /// Undo executed instructions until only `step` instructions have been executed. Returns
+    /// `false`, having rewound as far as possible, if the history doesn't go back that far.
+    pub fn rewind_to(&mut self, step: usize) -> bool {
+        while self.steps > step {
+            if self.step_back().is_none() {
+                return false;
+            }
+        }
+
+        self.steps == step
+    }
+
+    /// The first cycle in the recorded trace, if any.
+    ///
+    /// An instruction about to be executed a second time only means the program is stuck when
+    /// control flow doesn't depend on the registers, as on the handheld.
+    pub fn cycle(&self) -> Option<Cycle> {
+        let trace = self.trace.as_ref()?;
+        let mut first_seen = HashMap::new();
+
+        let ips = trace
+            .iter()
+            .map(|entry| entry.ip as isize)
+            .chain(std::iter::once(self.ip));
+
+        for (idx, ip) in ips.enumerate() {
+            if let Some(&start) = first_seen.get(&ip) {
+                let offset = self.steps - trace.len();
+
+                return Some(Cycle {
+                    start: offset + start,
+                    entry: ip as usize,
+                    body: trace[start..idx].to_vec(),
+                });
+            }
+
+            first_seen.insert(ip, idx);
+        }
+
+        None
+    }
+
+    fn check_halt(&self) -> Option<HaltStatus> {
+        let executed = usize::try_from(self.ip)
+            .ok()
+            .and_then(|ip| self.execution_counts.get(ip))
+            .copied()
+            .unwrap_or(0);
+
+        self.policy
+            .check(self.ip, self.program.len(), self.steps, executed)
+    }
+
+    /// Execute a single instruction, unless the VM has halted or is about to.
+    pub fn step(&mut self) -> Event {
+        if let Some(status) = self.halt_status.or_else(|| self.check_halt()) {
+            self.halt_status = Some(status);
+
+            return Event::Halted(status);
+        }
+
+        let ip = self.ip as usize;
+        let op = &self.program[ip];
+        let previous = self.registers.clone();
+
+        let flow = if self.isa.accepts(op) {
+            (self.isa.opcodes()[op.opcode].execute)(&mut self.registers, &op.operands)
+        } else {
+            None
+        };
+        let flow = match flow {
+            Some(flow) => flow,
+            None => {
+                self.registers = previous;
+                let status = HaltStatus::InvalidInstruction(ip);
+                self.halt_status = Some(status);
+
+                return Event::Halted(status);
+            }
+        };
+
+        let acc_delta = self.acc() - previous.first().copied().unwrap_or(0);
+        let op = op.clone();
+
+        self.execution_counts[ip] += 1;
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceEntry {
+                ip,
+                op: op.clone(),
+                acc_delta,
+                registers: previous,
+            });
+        }
+        self.steps += 1;
+
+        match flow {
+            Flow::Next => self.ip += 1,
+            Flow::Jump(offset) => self.ip += offset,
+            Flow::Halt => self.halt_status = Some(HaltStatus::Done),
+        }
+
+        Event::Executed { ip, op, acc_delta }
+    }
+
+    /// Run until the VM halts.
+    pub fn run(&mut self) -> HaltStatus {
+        loop {
+            if let Event::Halted(status) = self.step() {
+                break status;
+            }
+        }
+    }
+
+    /// Run until the VM halts or `limit` more instructions have been executed, in which case
+    /// [`HaltStatus::StepLimit`] is returned but the VM can be resumed.
+    pub fn run_with_limit(&mut self, limit: usize) -> HaltStatus {
+        for _ in 0..limit {
+            if let Event::Halted(status) = self.step() {
+                return status;
+            }
+        }
+
+        self.halt_status
+            .or_else(|| self.check_halt())
+            .unwrap_or(HaltStatus::StepLimit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_lines;
+
+    const INPUT: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+    fn load(input: &str, policy: HaltPolicy) -> VM {
+        VM::with_policy(parse_lines(input).collect(), policy)
+    }
+
+    #[test]
+    fn test_step() {
+        let mut vm = load(INPUT, HaltPolicy::default());
+
+        assert_eq!(
+            vm.step(),
+            Event::Executed {
+                ip: 0,
+                op: Instruction::Nop(0).into(),
+                acc_delta: 0
+            }
+        );
+        assert_eq!(
+            vm.step(),
+            Event::Executed {
+                ip: 1,
+                op: Instruction::Acc(1).into(),
+                acc_delta: 1
+            }
+        );
+        assert_eq!((vm.ip(), vm.acc(), vm.steps()), (2, 1, 2));
+        assert_eq!(vm.run(), HaltStatus::InfiniteLoop);
+        assert_eq!(vm.acc(), 5);
+        assert_eq!(vm.step(), Event::Halted(HaltStatus::InfiniteLoop));
+        assert_eq!(vm.halt_status(), Some(HaltStatus::InfiniteLoop));
+    }
+
+    #[test]
+    fn test_run_with_limit() {
+        let mut vm = load(INPUT, HaltPolicy::default());
+
+        assert_eq!(vm.run_with_limit(3), HaltStatus::StepLimit);
+        assert_eq!(vm.steps(), 3);
+        assert_eq!(vm.halt_status(), None);
+        assert_eq!(vm.run_with_limit(100), HaltStatus::InfiniteLoop);
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let policy = HaltPolicy {
+            detect_loops: false,
+            max_steps: Some(20),
+            ..HaltPolicy::default()
+        };
+        let mut vm = load(INPUT, policy);
+
+        assert_eq!(vm.run(), HaltStatus::StepLimit);
+        assert_eq!(vm.steps(), 20);
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut vm = load("acc +1\njmp -2", HaltPolicy::default());
+        assert_eq!(vm.run(), HaltStatus::OutOfBounds(-1));
+        assert_eq!(vm.acc(), 1);
+
+        let mut vm = load("jmp +5\nacc +1", HaltPolicy::default());
+        assert_eq!(vm.run(), HaltStatus::Done);
+
+        let policy = HaltPolicy {
+            allow_jump_past_end: false,
+            ..HaltPolicy::default()
+        };
+        let mut vm = load("jmp +5\nacc +1", policy);
+        assert_eq!(vm.run(), HaltStatus::OutOfBounds(5));
+
+        let mut vm = load("jmp +1\nacc +1", policy);
+        assert_eq!(vm.run(), HaltStatus::Done);
+    }
+
+    #[test]
+    fn test_patch() {
+        let mut vm = load(INPUT, HaltPolicy::default());
+
+        assert_eq!(vm.run(), HaltStatus::InfiniteLoop);
+        assert_eq!(
+            vm.patch(7, Instruction::Nop(-4)),
+            Some(Instruction::Jmp(-4).into())
+        );
+        assert_eq!(vm.patch(9, Instruction::Nop(0)), None);
+        assert_eq!(vm.halt_status(), None);
+
+        // The loop is broken, so carrying on from where it was detected finishes the program
+        assert_eq!(vm.run(), HaltStatus::Done);
+        assert_eq!(vm.acc(), 13);
+
+        // Patching in a new loop is still detected
+        let mut vm = load(INPUT, HaltPolicy::default());
+        vm.enable_trace();
+        assert_eq!(vm.run(), HaltStatus::InfiniteLoop);
+        vm.patch(2, Instruction::Jmp(-1));
+        assert_eq!(vm.run(), HaltStatus::InfiniteLoop);
+        assert_eq!((vm.ip(), vm.acc()), (1, 6));
+        assert!(vm.rewind_to(0));
+        assert_eq!(vm.acc(), 0);
+    }
+
+    #[test]
+    fn test_instruction_display() {
+        assert_eq!(Instruction::Nop(0).to_string(), "nop +0");
+        assert_eq!(Instruction::Jmp(-3).to_string(), "jmp -3");
+        assert_eq!("acc -99".parse(), Ok(Instruction::Acc(-99)));
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut vm = load(INPUT, HaltPolicy::default());
+        assert_eq!(vm.step_back(), None);
+
+        vm.enable_trace();
+        assert_eq!(vm.run(), HaltStatus::InfiniteLoop);
+        assert_eq!((vm.ip(), vm.acc(), vm.steps()), (1, 5, 7));
+
+        assert_eq!(
+            vm.step_back(),
+            Some(TraceEntry {
+                ip: 4,
+                op: Instruction::Jmp(-3).into(),
+                acc_delta: 0,
+                registers: vec![5]
+            })
+        );
+        assert_eq!(vm.halt_status(), None);
+        assert_eq!((vm.ip(), vm.acc(), vm.steps()), (4, 5, 6));
+
+        assert!(vm.rewind_to(2));
+        assert_eq!((vm.ip(), vm.acc(), vm.steps()), (2, 1, 2));
+        assert_eq!(vm.trace().map(|t| t.len()), Some(2));
+
+        assert_eq!(vm.run(), HaltStatus::InfiniteLoop);
+        assert_eq!(vm.acc(), 5);
+    }
+
+    #[test]
+    fn test_rewind_past_trace() {
+        let mut vm = load(INPUT, HaltPolicy::default());
+
+        vm.run_with_limit(2);
+        vm.enable_trace();
+        vm.run();
+
+        assert!(!vm.rewind_to(0));
+        assert_eq!(vm.steps(), 2);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut vm = load(INPUT, HaltPolicy::default());
+        vm.enable_trace();
+        vm.run();
+
+        let cycle = vm.cycle().expect("Program loops");
+        assert_eq!((cycle.start, cycle.entry, cycle.acc_delta()), (1, 1, 5));
+        assert_eq!(
+            cycle.display(vm.isa()).to_string(),
+            "Cycle entered at ip 1 after 1 steps, 6 instructions long, acc +5 per iteration
+     1: acc +1     acc +1
+     2: jmp +4     acc +0
+     6: acc +1     acc +1
+     7: jmp -4     acc +0
+     3: acc +3     acc +3
+     4: jmp -3     acc +0
+"
+        );
+
+        let policy = HaltPolicy {
+            detect_loops: false,
+            max_steps: Some(50),
+            ..HaltPolicy::default()
+        };
+        let mut vm = load(INPUT, policy);
+        vm.enable_trace();
+        vm.run();
+        assert_eq!(vm.cycle().map(|c| c.body.len()), Some(6));
+
+        let mut vm = load("nop +0\nacc +1", HaltPolicy::default());
+        vm.enable_trace();
+        assert_eq!(vm.run(), HaltStatus::Done);
+        assert_eq!(vm.cycle(), None);
+    }
+}
@@ -0,0 +1,420 @@
+use std::fmt::{self, Write};
+
+use super::cfg::ControlFlowGraph;
+use super::Instruction;
+
+/// A maximal run of instructions that's only entered at its first instruction and only left
+/// after its last one.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,
+    /// The last instruction of the block, inclusive.
+    pub end: usize,
+    pub acc_delta: isize,
+    /// The address executed after the block, which might be outside of the program.
+    pub successor: isize,
+}
+
+/// A cycle in the control flow graph, which a program never leaves once it's entered.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Loop {
+    /// The instructions of the cycle in execution order, starting at the lowest address.
+    pub body: Vec<usize>,
+    pub acc_delta: isize,
+}
+
+/// The result of analysing a console program without running it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Analysis {
+    pub instructions: Vec<Instruction>,
+    /// Instructions never executed when starting at 0, ordered by address.
+    pub unreachable: Vec<usize>,
+    /// Jumps, as `(ip, target)`, that go before the start or past the end of the program.
+    /// Jumping to exactly one past the last instruction is the normal way to terminate.
+    pub out_of_range: Vec<(usize, isize)>,
+    /// Every cycle in the control flow graph, ordered by their lowest address.
+    pub loops: Vec<Loop>,
+    pub blocks: Vec<BasicBlock>,
+    /// Whether starting at 0 always runs off the end of the program.
+    pub terminates: bool,
+}
+
+fn acc_delta(instructions: &[Instruction]) -> isize {
+    instructions
+        .iter()
+        .map(|instruction| match instruction {
+            Instruction::Acc(delta) => *delta,
+            _ => 0,
+        })
+        .sum()
+}
+
+fn blocks(cfg: &ControlFlowGraph) -> Vec<BasicBlock> {
+    let instructions = cfg.instructions();
+    let mut leaders = vec![false; instructions.len()];
+
+    for (ip, instruction) in instructions.iter().enumerate() {
+        if ip == 0 {
+            leaders[ip] = true;
+        }
+
+        if instruction.is_jmp() {
+            let target = cfg.successor(ip);
+            if target >= 0 && (target as usize) < instructions.len() {
+                leaders[target as usize] = true;
+            }
+            if ip + 1 < instructions.len() {
+                leaders[ip + 1] = true;
+            }
+        }
+    }
+
+    let starts: Vec<_> = (0..instructions.len()).filter(|&ip| leaders[ip]).collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(idx, &start)| {
+            let end = starts
+                .get(idx + 1)
+                .map(|next| next - 1)
+                .unwrap_or(instructions.len() - 1);
+
+            BasicBlock {
+                start,
+                end,
+                acc_delta: acc_delta(&instructions[start..=end]),
+                successor: cfg.successor(end),
+            }
+        })
+        .collect()
+}
+
+fn loops(cfg: &ControlFlowGraph) -> Vec<Loop> {
+    let len = cfg.instructions().len();
+    // The walk from which every instruction was first visited
+    let mut visited_by: Vec<Option<usize>> = vec![None; len];
+    let mut loops = vec![];
+
+    for walk in 0..len {
+        let mut path = vec![];
+        let mut ip = walk as isize;
+
+        while ip >= 0 && (ip as usize) < len && visited_by[ip as usize].is_none() {
+            visited_by[ip as usize] = Some(walk);
+            path.push(ip as usize);
+            ip = cfg.successor(ip as usize);
+        }
+
+        // Only a walk that runs into itself has found a new cycle
+        if ip >= 0 && (ip as usize) < len && visited_by[ip as usize] == Some(walk) {
+            let entry = path.iter().position(|&p| p == ip as usize).unwrap();
+            let mut body = path.split_off(entry);
+            let lowest = (0..body.len()).min_by_key(|&i| body[i]).unwrap();
+            body.rotate_left(lowest);
+
+            let acc_delta = body
+                .iter()
+                .map(|&ip| acc_delta(&cfg.instructions()[ip..=ip]))
+                .sum();
+
+            loops.push(Loop { body, acc_delta });
+        }
+    }
+
+    loops.sort_by_key(|l| l.body[0]);
+
+    loops
+}
+
+impl Analysis {
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        let instructions = cfg.instructions();
+        let len = instructions.len();
+
+        let mut reachable = vec![false; len];
+        for ip in cfg.reachable_from_start() {
+            reachable[ip] = true;
+        }
+
+        let out_of_range = instructions
+            .iter()
+            .enumerate()
+            .filter(|(_, instruction)| instruction.is_jmp())
+            .map(|(ip, _)| (ip, cfg.successor(ip)))
+            .filter(|&(_, target)| target < 0 || target > len as isize)
+            .collect();
+
+        Self {
+            instructions: instructions.to_vec(),
+            unreachable: (0..len).filter(|&ip| !reachable[ip]).collect(),
+            out_of_range,
+            loops: loops(cfg),
+            blocks: blocks(cfg),
+            terminates: cfg.terminating().first().copied().unwrap_or(true),
+        }
+    }
+
+    fn describe_target(&self, target: isize) -> String {
+        if target == self.instructions.len() as isize {
+            "end".to_string()
+        } else if target < 0 || target > self.instructions.len() as isize {
+            format!("{} (out of range)", target)
+        } else {
+            target.to_string()
+        }
+    }
+
+    /// The control flow graph of basic blocks in Graphviz DOT format.
+    ///
+    /// Unreachable blocks are dashed and edges that are part of a cycle are red.
+    pub fn to_dot(&self) -> String {
+        let len = self.instructions.len() as isize;
+        let in_loop = |ip: usize| self.loops.iter().any(|l| l.body.contains(&ip));
+        let mut output = String::new();
+
+        // Writing to a `String` can't fail
+        writeln!(output, "digraph program {{").unwrap();
+        writeln!(output, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        writeln!(output, "    start [shape=circle];").unwrap();
+        writeln!(output, "    end [shape=doublecircle];").unwrap();
+
+        if self.out_of_range.iter().any(|&(_, target)| target < 0) {
+            writeln!(output, "    invalid [shape=octagon];").unwrap();
+        }
+
+        for block in &self.blocks {
+            let label: String = (block.start..=block.end)
+                .map(|ip| format!("{}: {}\\l", ip, self.instructions[ip]))
+                .collect();
+            let style = if self.unreachable.contains(&block.start) {
+                ", style=dashed"
+            } else {
+                ""
+            };
+
+            writeln!(
+                output,
+                "    b{} [label=\"{}\"{}];",
+                block.start, label, style
+            )
+            .unwrap();
+        }
+
+        if !self.blocks.is_empty() {
+            writeln!(output, "    start -> b0;").unwrap();
+        } else {
+            writeln!(output, "    start -> end;").unwrap();
+        }
+
+        for block in &self.blocks {
+            let target = if block.successor >= len {
+                "end".to_string()
+            } else if block.successor < 0 {
+                "invalid".to_string()
+            } else {
+                format!("b{}", block.successor)
+            };
+
+            let mut attributes = vec![];
+            if block.successor < 0 || block.successor > len {
+                attributes.push(format!("label=\"{}\"", block.successor));
+            }
+            if in_loop(block.end) {
+                attributes.push("color=red".to_string());
+            }
+
+            if attributes.is_empty() {
+                writeln!(output, "    b{} -> {};", block.start, target).unwrap();
+            } else {
+                writeln!(
+                    output,
+                    "    b{} -> {} [{}];",
+                    block.start,
+                    target,
+                    attributes.join(", ")
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(output, "}}").unwrap();
+
+        output
+    }
+}
+
+fn join(addresses: impl Iterator<Item = String>) -> String {
+    let joined = addresses.collect::<Vec<_>>().join(", ");
+
+    if joined.is_empty() {
+        "none".to_string()
+    } else {
+        joined
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} instructions in {} basic blocks",
+            self.instructions.len(),
+            self.blocks.len()
+        )?;
+        writeln!(
+            f,
+            "Termination guaranteed: {}",
+            if self.terminates { "yes" } else { "no" }
+        )?;
+        writeln!(
+            f,
+            "Unreachable instructions: {}",
+            join(self.unreachable.iter().map(|ip| ip.to_string()))
+        )?;
+        writeln!(
+            f,
+            "Out of range jumps: {}",
+            join(
+                self.out_of_range
+                    .iter()
+                    .map(|(ip, target)| format!("{} -> {}", ip, target))
+            )
+        )?;
+
+        writeln!(f, "Cycles:")?;
+        if self.loops.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for l in &self.loops {
+            let body: Vec<_> = l.body.iter().map(|ip| ip.to_string()).collect();
+
+            writeln!(
+                f,
+                "  {} -> {}, acc {:+} per iteration",
+                body.join(" -> "),
+                l.body[0],
+                l.acc_delta
+            )?;
+        }
+
+        writeln!(f, "Basic blocks:")?;
+        for block in &self.blocks {
+            writeln!(
+                f,
+                "  {:>5}..={:<5} acc {:<+6} -> {}",
+                block.start,
+                block.end,
+                block.acc_delta,
+                self.describe_target(block.successor)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_lines;
+
+    const INPUT: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+    fn analyze(input: &str) -> Analysis {
+        Analysis::new(&ControlFlowGraph::new(
+            &parse_lines::<Instruction>(input).collect::<Vec<_>>(),
+        ))
+    }
+
+    #[test]
+    fn test_analysis() {
+        let analysis = analyze(INPUT);
+
+        assert!(!analysis.terminates);
+        assert_eq!(analysis.unreachable, vec![5, 8]);
+        assert_eq!(analysis.out_of_range, vec![]);
+        assert_eq!(
+            analysis.loops,
+            vec![Loop {
+                body: vec![1, 2, 6, 7, 3, 4],
+                acc_delta: 5
+            }]
+        );
+        assert_eq!(
+            analysis
+                .blocks
+                .iter()
+                .map(|b| (b.start, b.end, b.acc_delta, b.successor))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, 0, 0, 1),
+                (1, 2, 1, 6),
+                (3, 4, 3, 1),
+                (5, 5, -99, 6),
+                (6, 7, 1, 3),
+                (8, 8, 6, 9)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_and_termination() {
+        let analysis = analyze("acc +1\njmp +4\njmp -5\njmp +0");
+
+        assert!(analysis.terminates);
+        assert_eq!(analysis.unreachable, vec![2, 3]);
+        assert_eq!(analysis.out_of_range, vec![(1, 5), (2, -3)]);
+        assert_eq!(
+            analysis.loops,
+            vec![Loop {
+                body: vec![3],
+                acc_delta: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        assert_eq!(
+            analyze("acc +2\njmp -1\nacc +1").to_string(),
+            "3 instructions in 2 basic blocks
+Termination guaranteed: no
+Unreachable instructions: 2
+Out of range jumps: none
+Cycles:
+  0 -> 1 -> 0, acc +2 per iteration
+Basic blocks:
+      0..=1     acc +2     -> 0
+      2..=2     acc +1     -> end
+"
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        assert_eq!(
+            analyze("acc +2\njmp +3\njmp -3").to_dot(),
+            r#"digraph program {
+    node [shape=box, fontname="monospace"];
+    start [shape=circle];
+    end [shape=doublecircle];
+    invalid [shape=octagon];
+    b0 [label="0: acc +2\l1: jmp +3\l"];
+    b2 [label="2: jmp -3\l", style=dashed];
+    start -> b0;
+    b0 -> end [label="4"];
+    b2 -> invalid [label="-1"];
+}
+"#
+        );
+    }
+}
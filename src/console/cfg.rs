@@ -0,0 +1,193 @@
+use std::collections::VecDeque;
+
+use super::Instruction;
+
+/// A single instruction change that makes a program terminate.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Repair {
+    pub ip: usize,
+    pub replacement: Instruction,
+}
+
+/// The control flow graph of a handheld console program.
+///
+/// Control flow doesn't depend on `acc` so every instruction has exactly one successor, which
+/// isn't true of other [`super::isa::InstructionSet`]s with conditional jumps. As with
+/// the default [`super::HaltPolicy`], any successor at or beyond the end of the program
+/// terminates it while successors before the start are dead ends.
+pub struct ControlFlowGraph {
+    instructions: Vec<Instruction>,
+}
+
+impl ControlFlowGraph {
+    pub fn new(instructions: &[Instruction]) -> Self {
+        Self {
+            instructions: instructions.to_vec(),
+        }
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// The address executed after `ip`, which might be outside of the program.
+    pub fn successor(&self, ip: usize) -> isize {
+        ip as isize + self.instructions[ip].ip_delta()
+    }
+
+    fn is_end(&self, target: isize) -> bool {
+        target >= self.instructions.len() as isize
+    }
+
+    fn predecessors(&self) -> (Vec<Vec<usize>>, Vec<usize>) {
+        let mut predecessors = vec![vec![]; self.instructions.len()];
+        let mut end_predecessors = vec![];
+
+        for ip in 0..self.instructions.len() {
+            let target = self.successor(ip);
+
+            if self.is_end(target) {
+                end_predecessors.push(ip);
+            } else if target >= 0 {
+                predecessors[target as usize].push(ip);
+            }
+        }
+
+        (predecessors, end_predecessors)
+    }
+
+    /// For every instruction, whether execution starting there runs off the end of the program.
+    pub fn terminating(&self) -> Vec<bool> {
+        let (predecessors, end_predecessors) = self.predecessors();
+        let mut terminating = vec![false; self.instructions.len()];
+        let mut queue: VecDeque<usize> = end_predecessors.into_iter().collect();
+
+        while let Some(ip) = queue.pop_front() {
+            if terminating[ip] {
+                continue;
+            }
+            terminating[ip] = true;
+
+            queue.extend(predecessors[ip].iter().filter(|&&p| !terminating[p]));
+        }
+
+        terminating
+    }
+
+    /// The instructions executed when starting at 0, in the order they're executed, up until the
+    /// program terminates or an instruction would be executed again.
+    pub fn reachable_from_start(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.instructions.len()];
+        let mut reachable = vec![];
+        let mut ip = 0_isize;
+
+        while ip >= 0 && !self.is_end(ip) && !seen[ip as usize] {
+            seen[ip as usize] = true;
+            reachable.push(ip as usize);
+            ip = self.successor(ip as usize);
+        }
+
+        reachable
+    }
+
+    /// Every `nop`/`jmp` swap that makes the program terminate, ordered by address.
+    ///
+    /// Execution only reaches a flipped instruction via the instructions reachable from the
+    /// start, and when the program doesn't terminate none of those can reach the end. The
+    /// flipped instruction is thus never executed again and the repair works exactly when its new
+    /// successor terminates in the original program. This makes finding repairs linear in the
+    /// length of the program.
+    ///
+    /// A program that already terminates needs no repair, so none are returned for it.
+    pub fn repairs(&self) -> Vec<Repair> {
+        let terminating = self.terminating();
+
+        if terminating.first().copied().unwrap_or(true) {
+            return vec![];
+        }
+
+        let mut repairs: Vec<_> = self
+            .reachable_from_start()
+            .into_iter()
+            .filter_map(|ip| {
+                let replacement = self.instructions[ip].flipped()?;
+                let target = ip as isize + replacement.ip_delta();
+
+                let terminates =
+                    self.is_end(target) || (target >= 0 && terminating[target as usize]);
+
+                if terminates {
+                    Some(Repair { ip, replacement })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        repairs.sort_by_key(|repair| repair.ip);
+
+        repairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_lines;
+
+    const INPUT: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+    fn cfg(input: &str) -> ControlFlowGraph {
+        ControlFlowGraph::new(&parse_lines::<Instruction>(input).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_terminating() {
+        assert_eq!(
+            cfg(INPUT).terminating(),
+            vec![false, false, false, false, false, false, false, false, true]
+        );
+        assert_eq!(cfg("jmp -1\nacc +1").terminating(), vec![false, true]);
+    }
+
+    #[test]
+    fn test_reachable_from_start() {
+        assert_eq!(cfg(INPUT).reachable_from_start(), vec![0, 1, 2, 6, 7, 3, 4]);
+        assert_eq!(cfg("acc +1\njmp +2").reachable_from_start(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_repairs() {
+        assert_eq!(
+            cfg(INPUT).repairs(),
+            vec![Repair {
+                ip: 7,
+                replacement: Instruction::Nop(-4)
+            }]
+        );
+
+        assert_eq!(
+            cfg("nop +3\njmp +0\nacc +1").repairs(),
+            vec![
+                Repair {
+                    ip: 0,
+                    replacement: Instruction::Jmp(3)
+                },
+                Repair {
+                    ip: 1,
+                    replacement: Instruction::Nop(0)
+                }
+            ]
+        );
+
+        assert_eq!(cfg("nop +0\nacc +1").repairs(), vec![]);
+        assert_eq!(cfg("acc +1\njmp -1\njmp -2").repairs(), vec![]);
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use super::{Event, VM};
+
+/// The number of instructions shown either side of `ip` when listing the program.
+const WINDOW: usize = 3;
+
+pub const HELP: &str = "Commands:
+  step [n]              Execute n instructions (default 1)
+  back [n]              Undo n instructions (default 1)
+  continue              Run until a breakpoint, watchpoint or halt
+  break <ip>            Toggle a breakpoint at ip
+  watch acc             Stop whenever acc changes
+  watch acc <value>     Stop when acc becomes value
+  unwatch               Remove all watchpoints
+  patch <ip> <instr>    Replace the instruction at ip, e.g. `patch 7 nop -4`
+  print                 Show ip, acc and the halt status
+  list [ip]             Disassemble around ip (default current ip)
+  cycle                 Show the cycle the program is stuck in, if any
+  help                  Show this help";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Watchpoint {
+    /// Stop whenever `acc` changes.
+    Change,
+    /// Stop when `acc` becomes the value.
+    Equals(isize),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    Step(usize),
+    Back(usize),
+    Continue,
+    Break(usize),
+    Watch(Watchpoint),
+    Unwatch,
+    /// Replace the instruction at an address with one written in the VM's assembly.
+    Patch(usize, String),
+    Print,
+    List(Option<usize>),
+    Cycle,
+    Help,
+}
+
+fn parse_count(value: Option<&str>) -> Result<usize, String> {
+    value
+        .map(|v| {
+            v.parse::<usize>()
+                .map_err(|e| format!("Invalid count `{}`. {}", v, e))
+        })
+        .unwrap_or(Ok(1))
+}
+
+fn parse_ip(value: Option<&str>) -> Result<usize, String> {
+    value
+        .ok_or_else(|| "Expected an instruction address".to_string())
+        .and_then(|v| {
+            v.parse::<usize>()
+                .map_err(|e| format!("Invalid address `{}`. {}", v, e))
+        })
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+
+        match parts.next() {
+            Some("step") | Some("s") => parse_count(parts.next()).map(Command::Step),
+            Some("back") | Some("b") => parse_count(parts.next()).map(Command::Back),
+            Some("continue") | Some("c") => Ok(Command::Continue),
+            Some("break") => parse_ip(parts.next()).map(Command::Break),
+            Some("watch") => match (parts.next(), parts.next()) {
+                (Some("acc"), None) => Ok(Command::Watch(Watchpoint::Change)),
+                (Some("acc"), Some(value)) => value
+                    .parse::<isize>()
+                    .map(|v| Command::Watch(Watchpoint::Equals(v)))
+                    .map_err(|e| format!("Invalid value `{}`. {}", value, e)),
+                _ => Err(format!("Can only watch `acc` in `{}`", s)),
+            },
+            Some("unwatch") => Ok(Command::Unwatch),
+            Some("patch") => {
+                let ip = parse_ip(parts.next())?;
+                let instruction = parts.collect::<Vec<_>>().join(" ");
+
+                Ok(Command::Patch(ip, instruction))
+            }
+            Some("print") | Some("p") => Ok(Command::Print),
+            Some("list") | Some("l") => match parts.next() {
+                Some(ip) => parse_ip(Some(ip)).map(|ip| Command::List(Some(ip))),
+                None => Ok(Command::List(None)),
+            },
+            Some("cycle") => Ok(Command::Cycle),
+            Some("help") | Some("h") => Ok(Command::Help),
+            _ => Err(format!("Unknown command `{}`", s)),
+        }
+    }
+}
+
+/// Drives a [`VM`] with breakpoints and watchpoints, producing text for every command.
+pub struct Debugger {
+    vm: VM,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl Debugger {
+    pub fn new(mut vm: VM) -> Self {
+        vm.enable_trace();
+
+        Self {
+            vm,
+            breakpoints: BTreeSet::new(),
+            watchpoints: vec![],
+        }
+    }
+
+    pub fn vm(&self) -> &VM {
+        &self.vm
+    }
+
+    pub fn execute(&mut self, command: Command) -> String {
+        match command {
+            Command::Step(n) => {
+                for _ in 0..n {
+                    if let Event::Halted(_) = self.vm.step() {
+                        break;
+                    }
+                }
+
+                self.status_and_listing()
+            }
+            Command::Back(n) => {
+                let undone = (0..n).take_while(|_| self.vm.step_back().is_some()).count();
+
+                if undone < n {
+                    format!("Reached start of history\n{}", self.status_and_listing())
+                } else {
+                    self.status_and_listing()
+                }
+            }
+            Command::Continue => {
+                let reason = self.continue_execution();
+
+                format!("{}\n{}", reason, self.status_and_listing())
+            }
+            Command::Break(ip) => {
+                if self.breakpoints.remove(&ip) {
+                    format!("Removed breakpoint at {}", ip)
+                } else {
+                    self.breakpoints.insert(ip);
+
+                    format!("Added breakpoint at {}", ip)
+                }
+            }
+            Command::Watch(watchpoint) => {
+                self.watchpoints.push(watchpoint);
+
+                match watchpoint {
+                    Watchpoint::Change => "Watching acc for changes".to_string(),
+                    Watchpoint::Equals(v) => format!("Watching for acc == {}", v),
+                }
+            }
+            Command::Unwatch => {
+                self.watchpoints.clear();
+
+                "Removed all watchpoints".to_string()
+            }
+            Command::Patch(ip, text) => {
+                let op = match self.vm.isa().assemble_line(&text) {
+                    Ok(op) => op,
+                    Err(e) => return e,
+                };
+                let replacement = self.vm.isa().format_op(&op);
+
+                match self.vm.patch(ip, op) {
+                    Some(previous) => format!(
+                        "Patched {}: {} -> {}",
+                        ip,
+                        self.vm.isa().format_op(&previous),
+                        replacement
+                    ),
+                    None => format!("No instruction at {}", ip),
+                }
+            }
+            Command::Print => self.status(),
+            Command::List(ip) => {
+                let ip = ip.map(|ip| ip as isize).unwrap_or_else(|| self.vm.ip());
+
+                self.listing(ip)
+            }
+            Command::Cycle => match self.vm.cycle() {
+                Some(cycle) => cycle.display(self.vm.isa()).to_string(),
+                None => "No cycle found".to_string(),
+            },
+            Command::Help => HELP.to_string(),
+        }
+    }
+
+    fn continue_execution(&mut self) -> String {
+        loop {
+            let acc_before = self.vm.acc();
+
+            if let Event::Halted(status) = self.vm.step() {
+                break format!("Halted: {:?}", status);
+            }
+
+            let acc = self.vm.acc();
+            let triggered = self.watchpoints.iter().find(|&&w| match w {
+                Watchpoint::Change => acc != acc_before,
+                Watchpoint::Equals(v) => acc != acc_before && acc == v,
+            });
+            if let Some(watchpoint) = triggered {
+                break format!(
+                    "Watchpoint {:?} triggered, acc {} -> {}",
+                    watchpoint, acc_before, acc
+                );
+            }
+
+            let ip = self.vm.ip();
+            if ip >= 0 && self.breakpoints.contains(&(ip as usize)) {
+                break format!("Breakpoint at {}", ip);
+            }
+        }
+    }
+
+    fn status(&self) -> String {
+        let status = match self.vm.halt_status() {
+            Some(status) => format!("halted: {:?}", status),
+            None => "running".to_string(),
+        };
+
+        let registers: Vec<_> = self
+            .vm
+            .isa()
+            .registers()
+            .iter()
+            .zip(self.vm.registers())
+            .map(|(name, value)| format!("{} = {}", name, value))
+            .collect();
+
+        format!(
+            "ip = {}, {}, steps = {}, {}",
+            self.vm.ip(),
+            registers.join(", "),
+            self.vm.steps(),
+            status
+        )
+    }
+
+    fn status_and_listing(&self) -> String {
+        format!("{}\n{}", self.status(), self.listing(self.vm.ip()))
+    }
+
+    fn listing(&self, around: isize) -> String {
+        let instructions = self.vm.program();
+        let mut output = String::new();
+
+        if instructions.is_empty() {
+            return "Empty program".to_string();
+        }
+
+        let center = around.clamp(0, instructions.len() as isize - 1) as usize;
+        let start = center.saturating_sub(WINDOW);
+        let end = (center + WINDOW + 1).min(instructions.len());
+
+        for (ip, instruction) in instructions.iter().enumerate().take(end).skip(start) {
+            let current = if ip as isize == self.vm.ip() {
+                "=>"
+            } else {
+                "  "
+            };
+            let breakpoint = if self.breakpoints.contains(&ip) {
+                '*'
+            } else {
+                ' '
+            };
+
+            // Writing to a `String` can't fail
+            writeln!(
+                output,
+                "{}{}{:>5}: {}",
+                current,
+                breakpoint,
+                ip,
+                self.vm.isa().format_op(instruction)
+            )
+            .unwrap();
+        }
+
+        output.trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::HaltPolicy;
+    use crate::parse_lines;
+
+    const INPUT: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+    fn debugger() -> Debugger {
+        Debugger::new(VM::with_policy(
+            parse_lines(INPUT).collect(),
+            HaltPolicy::default(),
+        ))
+    }
+
+    fn run(debugger: &mut Debugger, command: &str) -> String {
+        debugger.execute(command.parse().expect("Valid command"))
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!("step".parse(), Ok(Command::Step(1)));
+        assert_eq!("s 3".parse(), Ok(Command::Step(3)));
+        assert_eq!("break 4".parse(), Ok(Command::Break(4)));
+        assert_eq!(
+            "watch acc -2".parse(),
+            Ok(Command::Watch(Watchpoint::Equals(-2)))
+        );
+        assert_eq!(
+            "patch 7 nop -4".parse(),
+            Ok(Command::Patch(7, "nop -4".to_string()))
+        );
+        assert!("break".parse::<Command>().is_err());
+        assert!("watch ip".parse::<Command>().is_err());
+        assert!("jump 3".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_step_and_list() {
+        let mut debugger = debugger();
+
+        assert_eq!(
+            run(&mut debugger, "step 2"),
+            "ip = 2, acc = 1, steps = 2, running
+       0: nop +0
+       1: acc +1
+=>     2: jmp +4
+       3: acc +3
+       4: jmp -3
+       5: acc -99"
+        );
+        assert_eq!(
+            run(&mut debugger, "back"),
+            "ip = 1, acc = 0, steps = 1, running
+       0: nop +0
+=>     1: acc +1
+       2: jmp +4
+       3: acc +3
+       4: jmp -3"
+        );
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = debugger();
+
+        assert_eq!(run(&mut debugger, "break 7"), "Added breakpoint at 7");
+        assert!(run(&mut debugger, "continue").starts_with("Breakpoint at 7\nip = 7, acc = 2"));
+        assert!(run(&mut debugger, "list").contains("=>*    7: jmp -4"));
+        assert!(run(&mut debugger, "continue").starts_with("Halted: InfiniteLoop"));
+        assert_eq!(run(&mut debugger, "break 7"), "Removed breakpoint at 7");
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut debugger = debugger();
+
+        run(&mut debugger, "watch acc 2");
+        assert!(run(&mut debugger, "continue")
+            .starts_with("Watchpoint Equals(2) triggered, acc 1 -> 2\nip = 7"));
+
+        run(&mut debugger, "unwatch");
+        run(&mut debugger, "watch acc");
+        assert!(run(&mut debugger, "continue")
+            .starts_with("Watchpoint Change triggered, acc 2 -> 5\nip = 4"));
+    }
+
+    #[test]
+    fn test_patch() {
+        let mut debugger = debugger();
+
+        assert_eq!(
+            run(&mut debugger, "patch 7 nop -4"),
+            "Patched 7: jmp -4 -> nop -4"
+        );
+        assert!(run(&mut debugger, "continue").starts_with("Halted: Done"));
+        assert_eq!(debugger.vm().acc(), 8);
+        assert_eq!(run(&mut debugger, "patch 9 nop +0"), "No instruction at 9");
+    }
+
+    #[test]
+    fn test_patch_after_loop() {
+        let mut debugger = debugger();
+
+        assert!(run(&mut debugger, "continue").starts_with("Halted: InfiniteLoop"));
+        run(&mut debugger, "patch 7 nop -4");
+        assert!(run(&mut debugger, "continue").starts_with("Halted: Done"));
+        assert_eq!(debugger.vm().acc(), 13);
+    }
+}
@@ -0,0 +1,563 @@
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use super::Instruction;
+use crate::parser_combinators::Diagnostic;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OperandKind {
+    /// One of the instruction set's registers, written by name.
+    Register,
+    /// A signed integer, e.g. `+4` or `-2`.
+    Immediate,
+    /// A jump target, written either as an offset relative to the instruction or as a label.
+    Target,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Operand {
+    Register(usize),
+    Immediate(isize),
+    /// A jump target relative to the instruction.
+    Target(isize),
+}
+
+impl Operand {
+    /// The value of the operand, reading registers from `registers`. `None` for a register
+    /// that doesn't exist.
+    pub fn value(&self, registers: &[isize]) -> Option<isize> {
+        match *self {
+            Operand::Register(r) => registers.get(r).copied(),
+            Operand::Immediate(v) | Operand::Target(v) => Some(v),
+        }
+    }
+
+    /// The index of a register operand, `None` if the operand isn't a register.
+    pub fn register(&self) -> Option<usize> {
+        match *self {
+            Operand::Register(r) => Some(r),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> OperandKind {
+        match self {
+            Operand::Register(_) => OperandKind::Register,
+            Operand::Immediate(_) => OperandKind::Immediate,
+            Operand::Target(_) => OperandKind::Target,
+        }
+    }
+}
+
+/// What a machine does after executing an instruction.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Flow {
+    Next,
+    /// Jump relative to the instruction.
+    Jump(isize),
+    Halt,
+}
+
+/// Executes an instruction given the registers and the instruction's operands. Returns `None`
+/// when the operands can't be executed, which halts the machine.
+pub type Semantics = fn(&mut [isize], &[Operand]) -> Option<Flow>;
+
+#[derive(Clone)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    pub operands: Vec<OperandKind>,
+    pub execute: Semantics,
+}
+
+/// An assembled instruction, `opcode` indexes the opcodes of its [`InstructionSet`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Op {
+    pub opcode: usize,
+    pub operands: Vec<Operand>,
+}
+
+/// The registers and opcodes of a console-style machine, whose programs run on a
+/// [`super::VM`].
+#[derive(Clone)]
+pub struct InstructionSet {
+    registers: Vec<&'static str>,
+    opcodes: Vec<Opcode>,
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.find([';', '#']).map(|i| &line[..i]).unwrap_or(line)
+}
+
+impl InstructionSet {
+    pub fn new(registers: &[&'static str]) -> Self {
+        Self {
+            registers: registers.to_vec(),
+            opcodes: vec![],
+        }
+    }
+
+    pub fn opcode(
+        mut self,
+        mnemonic: &'static str,
+        operands: &[OperandKind],
+        execute: Semantics,
+    ) -> Self {
+        self.opcodes.push(Opcode {
+            mnemonic,
+            operands: operands.to_vec(),
+            execute,
+        });
+
+        self
+    }
+
+    /// The instruction set of the day08 handheld game console. Its opcodes are in the same
+    /// order as the variants of [`Instruction`].
+    pub fn handheld() -> Self {
+        Self::new(&["acc"])
+            .opcode("nop", &[OperandKind::Immediate], |_, _| Some(Flow::Next))
+            .opcode("acc", &[OperandKind::Immediate], |registers, operands| {
+                registers[0] += operands[0].value(registers)?;
+
+                Some(Flow::Next)
+            })
+            .opcode("jmp", &[OperandKind::Target], |registers, operands| {
+                Some(Flow::Jump(operands[0].value(registers)?))
+            })
+    }
+
+    pub fn registers(&self) -> &[&'static str] {
+        &self.registers
+    }
+
+    pub fn register(&self, name: &str) -> Option<usize> {
+        self.registers.iter().position(|&r| r == name)
+    }
+
+    pub fn opcodes(&self) -> &[Opcode] {
+        &self.opcodes
+    }
+
+    /// Whether `op` is an instruction of this set with the operands its opcode declares, i.e.
+    /// one the assembler could have produced.
+    pub fn accepts(&self, op: &Op) -> bool {
+        self.opcodes.get(op.opcode).is_some_and(|opcode| {
+            opcode.operands.len() == op.operands.len()
+                && opcode
+                    .operands
+                    .iter()
+                    .zip(&op.operands)
+                    .all(|(&kind, operand)| {
+                        operand.kind() == kind
+                            && operand.register().is_none_or(|r| r < self.registers.len())
+                    })
+        })
+    }
+
+    /// Assemble a single instruction, e.g. `jnz a, -2`. Jump targets have to be offsets as
+    /// there are no labels.
+    pub fn assemble_line(&self, text: &str) -> Result<Op, String> {
+        self.assemble_instruction(0, strip_comment(text).trim(), &HashMap::new())
+    }
+
+    /// Assemble a program written one instruction per line, e.g. `jnz a, loop`.
+    ///
+    /// Operands are separated by whitespace or commas. Everything after `;` or `#` is a comment.
+    /// A line can start with one or more labels, e.g. `loop:`, which refer to the next
+    /// instruction, or the end of the program if there is none.
+    pub fn assemble(&self, source: &str) -> Result<Vec<Op>, Vec<Diagnostic>> {
+        let mut diagnostics = vec![];
+        let mut labels = HashMap::new();
+        let mut lines = vec![];
+
+        for (idx, raw) in source.lines().enumerate() {
+            let mut line = strip_comment(raw).trim();
+
+            while let Some((label, rest)) = line.split_once(':') {
+                let label = label.trim();
+                if !is_label(label) {
+                    break;
+                }
+
+                if labels.insert(label, lines.len()).is_some() {
+                    diagnostics.push(Diagnostic {
+                        line: idx + 1,
+                        text: raw.trim().to_string(),
+                        message: format!("Duplicate label `{}`", label),
+                    });
+                }
+                line = rest.trim();
+            }
+
+            if !line.is_empty() {
+                lines.push((idx + 1, raw.trim(), line));
+            }
+        }
+
+        let program: Vec<_> = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(ip, &(line, raw, text))| {
+                self.assemble_instruction(ip, text, &labels)
+                    .map_err(|message| {
+                        diagnostics.push(Diagnostic {
+                            line,
+                            text: raw.to_string(),
+                            message,
+                        })
+                    })
+                    .ok()
+            })
+            .collect();
+
+        if diagnostics.is_empty() {
+            Ok(program)
+        } else {
+            diagnostics.sort_by_key(|d| d.line);
+
+            Err(diagnostics)
+        }
+    }
+
+    fn assemble_instruction(
+        &self,
+        ip: usize,
+        text: &str,
+        labels: &HashMap<&str, usize>,
+    ) -> Result<Op, String> {
+        let mut tokens = text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty());
+
+        let mnemonic = tokens.next().unwrap_or("");
+        let opcode = self
+            .opcodes
+            .iter()
+            .position(|o| o.mnemonic == mnemonic)
+            .ok_or_else(|| format!("Unknown instruction `{}`", mnemonic))?;
+
+        let tokens: Vec<_> = tokens.collect();
+        let kinds = &self.opcodes[opcode].operands;
+        if tokens.len() != kinds.len() {
+            return Err(format!(
+                "`{}` takes {} operands, found {}",
+                mnemonic,
+                kinds.len(),
+                tokens.len()
+            ));
+        }
+
+        let operands = kinds
+            .iter()
+            .zip(tokens)
+            .map(|(kind, token)| match kind {
+                OperandKind::Register => self
+                    .register(token)
+                    .map(Operand::Register)
+                    .ok_or_else(|| format!("Unknown register `{}`", token)),
+                OperandKind::Immediate => token
+                    .parse()
+                    .map(Operand::Immediate)
+                    .map_err(|e| format!("Invalid value `{}`. {}", token, e)),
+                OperandKind::Target => token
+                    .parse()
+                    .ok()
+                    .or_else(|| {
+                        labels
+                            .get(token)
+                            .map(|&target| target as isize - ip as isize)
+                    })
+                    .map(Operand::Target)
+                    .ok_or_else(|| format!("Unknown label `{}`", token)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Op { opcode, operands })
+    }
+
+    /// An instruction as assembly, with jump targets as offsets, e.g. `jnz a, -2`.
+    pub fn format_op(&self, op: &Op) -> String {
+        self.format_operands(op, |offset| format!("{:+}", offset))
+    }
+
+    fn format_operands(&self, op: &Op, target: impl Fn(isize) -> String) -> String {
+        let mnemonic = self
+            .opcodes
+            .get(op.opcode)
+            .map_or("???", |opcode| opcode.mnemonic);
+        let operands: Vec<_> = op
+            .operands
+            .iter()
+            .map(|operand| match *operand {
+                Operand::Register(r) => self
+                    .registers
+                    .get(r)
+                    .map_or_else(|| format!("r{}", r), |name| name.to_string()),
+                Operand::Immediate(v) => format!("{:+}", v),
+                Operand::Target(offset) => target(offset),
+            })
+            .collect();
+
+        format!("{} {}", mnemonic, operands.join(", "))
+            .trim_end()
+            .to_string()
+    }
+
+    /// Turn `program` back into assembly, which assembles to the same program. Jump targets
+    /// within the program get generated labels.
+    pub fn disassemble(&self, program: &[Op]) -> String {
+        let target = |ip: usize, offset: isize| {
+            usize::try_from(ip as isize + offset)
+                .ok()
+                .filter(|&t| t <= program.len())
+        };
+
+        let labels: BTreeSet<usize> = program
+            .iter()
+            .enumerate()
+            .flat_map(|(ip, op)| {
+                op.operands.iter().filter_map(move |operand| match operand {
+                    Operand::Target(offset) => target(ip, *offset),
+                    _ => None,
+                })
+            })
+            .collect();
+
+        let mut output = String::new();
+
+        // Writing to a `String` can't fail
+        for (ip, op) in program.iter().enumerate() {
+            if labels.contains(&ip) {
+                writeln!(output, "L{}:", ip).unwrap();
+            }
+
+            let text = self.format_operands(op, |offset| match target(ip, offset) {
+                Some(t) => format!("L{}", t),
+                None => format!("{:+}", offset),
+            });
+            writeln!(output, "    {}", text).unwrap();
+        }
+
+        if labels.contains(&program.len()) {
+            writeln!(output, "L{}:", program.len()).unwrap();
+        }
+
+        output
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl From<Instruction> for Op {
+    /// The instruction assembled for [`InstructionSet::handheld`].
+    fn from(instruction: Instruction) -> Self {
+        let (opcode, operand) = match instruction {
+            Instruction::Nop(v) => (0, Operand::Immediate(v)),
+            Instruction::Acc(v) => (1, Operand::Immediate(v)),
+            Instruction::Jmp(v) => (2, Operand::Target(v)),
+        };
+
+        Op {
+            opcode,
+            operands: vec![operand],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::{HaltPolicy, HaltStatus, VM};
+
+    const HANDHELD: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+    fn arithmetic() -> InstructionSet {
+        use OperandKind::*;
+
+        InstructionSet::new(&["a", "b"])
+            .opcode("set", &[Register, Immediate], |registers, operands| {
+                registers[operands[0].register()?] = operands[1].value(registers)?;
+
+                Some(Flow::Next)
+            })
+            .opcode("add", &[Register, Immediate], |registers, operands| {
+                registers[operands[0].register()?] += operands[1].value(registers)?;
+
+                Some(Flow::Next)
+            })
+            .opcode("dec", &[Register], |registers, operands| {
+                registers[operands[0].register()?] -= 1;
+
+                Some(Flow::Next)
+            })
+            .opcode("jnz", &[Register, Target], |registers, operands| {
+                if operands[0].value(registers)? != 0 {
+                    Some(Flow::Jump(operands[1].value(registers)?))
+                } else {
+                    Some(Flow::Next)
+                }
+            })
+            .opcode("hlt", &[], |_, _| Some(Flow::Halt))
+    }
+
+    #[test]
+    fn test_handheld() {
+        let isa = InstructionSet::handheld();
+        let program = isa.assemble(HANDHELD).unwrap();
+
+        assert_eq!(
+            program[2],
+            Op {
+                opcode: 2,
+                operands: vec![Operand::Target(4)]
+            }
+        );
+
+        let mut vm = VM::with_isa(isa, program, HaltPolicy::default());
+        assert_eq!(vm.run(), HaltStatus::InfiniteLoop);
+        assert_eq!(vm.register("acc"), Some(5));
+        assert_eq!(vm.acc(), 5);
+    }
+
+    #[test]
+    fn test_multiple_registers() {
+        let isa = arithmetic();
+        let program = isa
+            .assemble(
+                "; multiply 3 by 4 into a
+    set b, 4
+loop:
+    add a, +3   # a += 3
+    dec b
+    jnz b, loop
+    hlt
+    add a, 100",
+            )
+            .unwrap();
+
+        let policy = HaltPolicy {
+            detect_loops: false,
+            ..HaltPolicy::default()
+        };
+        let mut vm = VM::with_isa(isa, program, policy);
+        assert_eq!(vm.run(), HaltStatus::Done);
+        assert_eq!(vm.registers(), &[12, 0]);
+        assert_eq!(vm.ip(), 4);
+        assert_eq!(vm.steps(), 14);
+    }
+
+    #[test]
+    fn test_invalid_op() {
+        let isa = arithmetic();
+        assert_eq!(Operand::Immediate(1).register(), None);
+        assert_eq!(Operand::Register(2).value(&[0, 0]), None);
+
+        // `dec 1` and `dec c` can't be assembled but can be built by hand
+        let program = vec![
+            isa.assemble_line("add a, +1").unwrap(),
+            Op {
+                opcode: 2,
+                operands: vec![Operand::Immediate(1)],
+            },
+        ];
+        assert!(!isa.accepts(&program[1]));
+
+        let mut vm = VM::with_isa(isa.clone(), program, HaltPolicy::default());
+        assert_eq!(vm.run(), HaltStatus::InvalidInstruction(1));
+        assert_eq!(vm.registers(), &[1, 0]);
+
+        let program = vec![Op {
+            opcode: 2,
+            operands: vec![Operand::Register(2)],
+        }];
+        let mut vm = VM::with_isa(isa, program, HaltPolicy::default());
+        assert_eq!(vm.run(), HaltStatus::InvalidInstruction(0));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let isa = arithmetic();
+        let program = isa
+            .assemble(
+                "start: set a 2
+    jnz a, end
+    jnz b, -5
+loop: dec a
+    jnz a loop
+    jnz b start
+end:",
+            )
+            .unwrap();
+
+        let disassembly = isa.disassemble(&program);
+        assert_eq!(
+            disassembly,
+            "L0:
+    set a, +2
+    jnz a, L6
+    jnz b, -5
+L3:
+    dec a
+    jnz a, L3
+    jnz b, L0
+L6:"
+        );
+        assert_eq!(isa.assemble(&disassembly), Ok(program));
+
+        let isa = InstructionSet::handheld();
+        let program = isa.assemble(HANDHELD).unwrap();
+        assert_eq!(isa.assemble(&isa.disassemble(&program)), Ok(program));
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let isa = arithmetic();
+        let errors = isa
+            .assemble(
+                "x: set a 1
+mul a 2
+dec
+jnz a nowhere
+x: set c 1
+set a one",
+            )
+            .unwrap_err();
+
+        let messages: Vec<_> = errors
+            .iter()
+            .map(|d| (d.line, d.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                (2, "Unknown instruction `mul`"),
+                (3, "`dec` takes 1 operands, found 0"),
+                (4, "Unknown label `nowhere`"),
+                (5, "Duplicate label `x`"),
+                (5, "Unknown register `c`"),
+                (6, "Invalid value `one`. invalid digit found in string"),
+            ]
+        );
+    }
+}
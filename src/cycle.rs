@@ -0,0 +1,308 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// The shape of the sequence of states produced by repeatedly applying a step function.
+///
+/// The first `prefix` states are never repeated, after which the sequence repeats every
+/// `period` states.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Cycle {
+    pub prefix: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    /// The first step whose state is the same as the state after `n` steps.
+    pub fn equivalent_step(&self, n: usize) -> usize {
+        if n < self.prefix {
+            n
+        } else {
+            self.prefix + (n - self.prefix) % self.period
+        }
+    }
+}
+
+/// Find the cycle with Floyd's tortoise and hare, keeping only two states in memory.
+pub fn floyd<S, F>(start: S, step: F) -> Cycle
+where
+    S: Clone + Eq,
+    F: Fn(&S) -> S,
+{
+    let mut tortoise = step(&start);
+    let mut hare = step(&tortoise);
+
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&step(&hare));
+    }
+
+    let mut prefix = 0;
+    tortoise = start;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix += 1;
+    }
+
+    let mut period = 1;
+    hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        period += 1;
+    }
+
+    Cycle { prefix, period }
+}
+
+/// Find the cycle with Brent's algorithm, which calls `step` less often than [`floyd`].
+pub fn brent<S, F>(start: S, step: F) -> Cycle
+where
+    S: Clone + Eq,
+    F: Fn(&S) -> S,
+{
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = start.clone();
+    let mut hare = step(&start);
+
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step(&hare);
+        period += 1;
+    }
+
+    tortoise = start.clone();
+    hare = start;
+    for _ in 0..period {
+        hare = step(&hare);
+    }
+
+    let mut prefix = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix += 1;
+    }
+
+    Cycle { prefix, period }
+}
+
+/// Find the cycle by remembering every state, calling `step` the fewest times.
+pub fn hashed<S, F>(start: S, step: F) -> Cycle
+where
+    S: Clone + Hash + Eq,
+    F: Fn(&S) -> S,
+{
+    hashed_by(start, step, S::clone)
+}
+
+/// Find the cycle by remembering the fingerprint of every state.
+///
+/// Two states are considered the same when their fingerprints are equal, so a fingerprint can
+/// leave out parts of the state that don't affect what comes next, e.g. a step counter, or be
+/// much smaller than a large state.
+pub fn hashed_by<S, F, K, P>(start: S, step: F, fingerprint: P) -> Cycle
+where
+    F: Fn(&S) -> S,
+    K: Hash + Eq,
+    P: Fn(&S) -> K,
+{
+    let mut detector = Detector::new();
+    let mut state = start;
+
+    loop {
+        if let Some(cycle) = detector.observe(fingerprint(&state)) {
+            break cycle;
+        }
+
+        state = step(&state);
+    }
+}
+
+/// A 64 bit hash of `value`, a fingerprint for states too large to remember whole.
+///
+/// **Note:** Different states can collide, with a tiny chance of finding a cycle that isn't
+/// there.
+pub fn fingerprint<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/// Finds the cycle from the fingerprints of states as they're produced, for states that are
+/// updated in place rather than by a step function, e.g. an automaton.
+#[derive(Debug, Clone)]
+pub struct Detector<K> {
+    seen: HashMap<K, usize>,
+}
+
+impl<K: Hash + Eq> Detector<K> {
+    pub fn new() -> Self {
+        Self {
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Record the fingerprint of the next state, starting with the initial one. Returns the
+    /// cycle once a fingerprint repeats, after which there's nothing more to find.
+    pub fn observe(&mut self, fingerprint: K) -> Option<Cycle> {
+        let n = self.seen.len();
+
+        match self.seen.get(&fingerprint) {
+            Some(&prefix) => Some(Cycle {
+                prefix,
+                period: n - prefix,
+            }),
+            None => {
+                self.seen.insert(fingerprint, n);
+
+                None
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq> Default for Detector<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Apply `step` to `start` `n` times, skipping whole iterations of `cycle`.
+pub fn state_after<S, F>(start: S, step: F, cycle: &Cycle, n: usize) -> S
+where
+    F: Fn(&S) -> S,
+{
+    (0..cycle.equivalent_step(n)).fold(start, |state, _| step(&state))
+}
+
+/// The state after applying `step` to `start` `n` times.
+///
+/// Stops stepping once a state repeats and computes the rest from the cycle, so `n` can be far
+/// larger than the number of distinct states.
+pub fn nth_state<S, F>(start: S, step: F, n: usize) -> S
+where
+    S: Clone + Hash + Eq,
+    F: Fn(&S) -> S,
+{
+    let mut states: Vec<S> = vec![];
+    let mut seen = HashMap::new();
+    let mut state = start;
+
+    for i in 0..=n {
+        if let Some(&prefix) = seen.get(&state) {
+            let cycle = Cycle {
+                prefix,
+                period: i - prefix,
+            };
+
+            return states.swap_remove(cycle.equivalent_step(n));
+        }
+
+        if i == n {
+            break;
+        }
+
+        seen.insert(state.clone(), i);
+        let next = step(&state);
+        states.push(state);
+        state = next;
+    }
+
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0, 1, 2, 3, 4, 5, 6, 3, 4, ...
+    fn step(x: &usize) -> usize {
+        if *x < 3 {
+            x + 1
+        } else {
+            3 + (x - 3 + 1) % 4
+        }
+    }
+
+    const CYCLE: Cycle = Cycle {
+        prefix: 3,
+        period: 4,
+    };
+
+    #[test]
+    fn test_detectors_agree() {
+        assert_eq!(floyd(0, step), CYCLE);
+        assert_eq!(brent(0, step), CYCLE);
+        assert_eq!(hashed(0, step), CYCLE);
+
+        assert_eq!(
+            floyd(5, step),
+            Cycle {
+                prefix: 0,
+                period: 4
+            }
+        );
+        assert_eq!(brent(5, step), floyd(5, step));
+        assert_eq!(hashed(5, step), floyd(5, step));
+
+        let fixed_point = Cycle {
+            prefix: 0,
+            period: 1,
+        };
+        assert_eq!(floyd(1, |x: &usize| *x), fixed_point);
+        assert_eq!(brent(1, |x: &usize| *x), fixed_point);
+        assert_eq!(hashed(1, |x: &usize| *x), fixed_point);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        // The counter never repeats, but doesn't affect the rest of the state
+        let cycle = hashed_by((0, 0), |&(x, count)| (step(&x), count + 1), |&(x, _)| x);
+
+        assert_eq!(cycle, CYCLE);
+    }
+
+    #[test]
+    fn test_detector() {
+        let mut detector = Detector::new();
+        let mut x = 0;
+
+        let cycle = loop {
+            if let Some(cycle) = detector.observe(fingerprint(&x)) {
+                break cycle;
+            }
+            x = step(&x);
+        };
+
+        assert_eq!(cycle, CYCLE);
+        assert_eq!(x, 3);
+    }
+
+    #[test]
+    fn test_jumping() {
+        assert_eq!(CYCLE.equivalent_step(2), 2);
+        assert_eq!(CYCLE.equivalent_step(7), 3);
+        assert_eq!(CYCLE.equivalent_step(1_000_000_000), 4);
+
+        assert_eq!(state_after(0, step, &CYCLE, 1_000_000_000), 4);
+        assert_eq!(nth_state(0, step, 1_000_000_000), 4);
+        assert_eq!(nth_state(0, step, 2), 2);
+        assert_eq!(nth_state(0, step, 0), 0);
+
+        for n in 0..20 {
+            assert_eq!(
+                nth_state(0, step, n),
+                (0..n).fold(0, |x, _| step(&x)),
+                "after {} steps",
+                n
+            );
+        }
+    }
+}
@@ -1,9 +1,14 @@
+use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::ops::Index;
 use std::str::FromStr;
 
+use crate::grid::{Boundary, Grid};
+use crate::math::Vector2;
+use crate::playback::Simulation;
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum Location {
+pub enum Location {
     Empty,
     Tree,
 }
@@ -20,58 +25,258 @@ impl TryFrom<char> for Location {
     }
 }
 
+impl From<Location> for char {
+    fn from(l: Location) -> Self {
+        match l {
+            Location::Tree => '#',
+            Location::Empty => '.',
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
-struct World {
-    locations: Vec<Vec<Location>>,
+pub struct World {
+    locations: Grid<Location>,
 }
 
-impl World {
-    fn is_past_end(&self, y: usize) -> bool {
-        y >= self.locations.len()
+impl FromStr for World {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(|locations: Grid<_>| Self {
+                // The same pattern repeats to the right many times
+                locations: locations.with_boundaries(Boundary::Wrap, Boundary::Bounded),
+            })
+            .map_err(|e| format!("Failed to parse world with error: {}", e))
     }
 }
 
-impl FromStr for World {
+impl Index<(usize, usize)> for World {
+    type Output = Location;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        let (x, y) = index;
+
+        &self.locations[Vector2::new(x as isize, y as isize)]
+    }
+}
+
+/// Going `right` cells, leftwards when negative, for every `down` cells.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Slope {
+    right: isize,
+    down: usize,
+}
+
+impl Slope {
+    /// **Note:** Fails if `down` is 0, the toboggan has to go down.
+    pub fn new(right: isize, down: usize) -> Result<Self, String> {
+        if down == 0 {
+            return Err("The toboggan has to go down".to_string());
+        }
+
+        Ok(Self { right, down })
+    }
+
+    pub fn right(&self) -> isize {
+        self.right
+    }
+
+    pub fn down(&self) -> usize {
+        self.down
+    }
+
+    /// Every slope going right by `-max_right..=max_right` for every `1..=max_down` down.
+    pub fn search_space(max_right: usize, max_down: usize) -> impl Iterator<Item = Slope> {
+        let max_right = max_right as isize;
+
+        (1..=max_down)
+            .flat_map(move |down| (-max_right..=max_right).map(move |right| Slope { right, down }))
+    }
+}
+
+/// Parses `right,down`, e.g. `3,1`.
+impl FromStr for Slope {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let locations_result: Result<Vec<Vec<_>>, _> = s
-            .lines()
-            .map(str::trim)
-            .filter(|s| s.len() > 0)
-            .map(|l| l.chars().map(|c| Location::try_from(c)).collect())
+        let invalid = || format!("Invalid slope `{}`, expected `right,down`", s);
+        let (right, down) = s.split_once(',').ok_or_else(invalid)?;
+
+        Self::new(
+            right.trim().parse().map_err(|_| invalid())?,
+            down.trim().parse().map_err(|_| invalid())?,
+        )
+    }
+}
+
+/// The way down a map at a slope.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Path {
+    pub slope: Slope,
+    /// Every location the toboggan stops at from the top to the bottom of the map. Columns
+    /// aren't wrapped, so they run past the width of the map.
+    pub visited: Vec<Vector2<isize>>,
+    /// The number of visited locations with a tree.
+    pub trees: usize,
+}
+
+impl World {
+    pub fn path(&self, slope: Slope) -> Path {
+        let mut trees = 0;
+        let visited = (0..)
+            .map(|step| Vector2::new(step * slope.right, step * slope.down as isize))
+            .map_while(|at| {
+                let location = self.locations.get(at)?;
+                if *location == Location::Tree {
+                    trees += 1;
+                }
+
+                Some(at)
+            })
+            .collect();
+
+        Path {
+            slope,
+            visited,
+            trees,
+        }
+    }
+
+    /// The path hitting the fewest trees, the first of them if several tie.
+    pub fn fewest_trees(&self, slopes: impl IntoIterator<Item = Slope>) -> Option<Path> {
+        slopes
+            .into_iter()
+            .map(|slope| self.path(slope))
+            .min_by_key(|path| path.trees)
+    }
+
+    /// The map with every location on `path` marked `X` when it's a tree and `O` otherwise.
+    /// Locations above or below the map, which only a hand built path has, are skipped.
+    pub fn render_path(&self, path: &Path) -> String {
+        let mut rows: Vec<Vec<char>> = self
+            .locations
+            .rows()
+            .map(|row| row.iter().map(|&l| char::from(l)).collect())
             .collect();
 
-        match locations_result {
-            Ok(locations) => Ok(Self { locations }),
-            Err(e) => Err(format!("Failed to parse world with error: {}", e)),
+        for &at in path.visited.iter() {
+            let x = at.x().rem_euclid(self.locations.width() as isize) as usize;
+            let cell = match usize::try_from(at.y()).ok().and_then(|y| rows.get_mut(y)) {
+                Some(row) => &mut row[x],
+                None => continue,
+            };
+
+            *cell = if *cell == char::from(Location::Tree) {
+                'X'
+            } else {
+                'O'
+            };
         }
+
+        rows.into_iter()
+            .map(|row| row.into_iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 }
 
-impl Index<(usize, usize)> for World {
-    type Output = Location;
+/// The number of rows above and below the toboggan shown in a frame.
+const VIEW_ROWS: usize = 10;
 
-    fn index(&self, index: (usize, usize)) -> &Self::Output {
-        let (mut x, y) = index;
-        x %= self.locations[0].len();
+/// The toboggan going down a slope a step at a time.
+pub struct TobogganSimulation {
+    world: World,
+    path: Path,
+    /// The index in `path.visited` of the toboggan.
+    steps: usize,
+    trees: usize,
+}
+
+pub fn toboggan_simulation(input: &str, slope: Slope) -> Result<TobogganSimulation, String> {
+    let world = input.parse::<World>()?;
+    if world.locations.height() == 0 {
+        return Err("Expected a non-empty map".to_string());
+    }
+
+    let path = world.path(slope);
+    let trees = (world[(0, 0)] == Location::Tree) as usize;
+
+    Ok(TobogganSimulation {
+        world,
+        path,
+        steps: 0,
+        trees,
+    })
+}
 
-        &self.locations[y][x]
+impl TobogganSimulation {
+    fn is_tree(&self, at: Vector2<isize>) -> bool {
+        self.world.locations.get(at) == Some(&Location::Tree)
     }
 }
 
-fn check_slope(world: &World, xdelta: usize, ydelta: usize) -> usize {
-    (0..)
-        .take_while(|&y| !world.is_past_end(y * ydelta))
-        .map(|y| world[(y * xdelta, y * ydelta)])
-        .filter(|&l| l == Location::Tree)
-        .count()
+impl Simulation for TobogganSimulation {
+    fn frame(&self) -> String {
+        let at = self.path.visited[self.steps];
+        let width = self.world.locations.width() as isize;
+        let y = at.y() as usize;
+        let rows =
+            y.saturating_sub(VIEW_ROWS)..(y + VIEW_ROWS + 1).min(self.world.locations.height());
+        let visited: HashSet<_> = self.path.visited[..=self.steps]
+            .iter()
+            .map(|&v| (v.x().rem_euclid(width) as usize, v.y() as usize))
+            .collect();
+
+        let view = rows
+            .map(|row| {
+                (0..width as usize)
+                    .map(|column| {
+                        let visited = visited.contains(&(column, row));
+
+                        match (visited, self.world[(column, row)]) {
+                            (true, _) if row == y => '@',
+                            (true, Location::Tree) => 'X',
+                            (true, Location::Empty) => 'O',
+                            (false, Location::Tree) => '#',
+                            (false, Location::Empty) => '.',
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            "Row {}/{} at column {}, {} trees hit\n\n{}",
+            y,
+            self.world.locations.height() - 1,
+            at.x(),
+            self.trees,
+            view
+        )
+    }
+
+    fn advance(&mut self) -> bool {
+        let at = match self.path.visited.get(self.steps + 1) {
+            Some(&at) => at,
+            None => return false,
+        };
+
+        self.steps += 1;
+        if self.is_tree(at) {
+            self.trees += 1;
+        }
+
+        true
+    }
 }
 
 pub fn star_one(input: &str) -> usize {
     let world = input.parse::<World>().expect("World should be parsable");
 
-    check_slope(&world, 3, 1)
+    world.path(Slope { right: 3, down: 1 }).trees
 }
 
 pub fn star_two(input: &str) -> usize {
@@ -79,13 +284,15 @@ pub fn star_two(input: &str) -> usize {
 
     [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)]
         .iter()
-        .map(|&(xdelta, ydelta)| check_slope(&world, xdelta, ydelta))
+        .map(|&(right, down)| world.path(Slope { right, down }).trees)
         .product()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{star_one, star_two};
+    use super::{star_one, star_two, toboggan_simulation, Slope, World};
+    use crate::math::Vector2;
+    use crate::playback::Simulation;
     const INPUT: &'static str = "..##.......
 #...#...#..
 .#....#..#.
@@ -107,4 +314,122 @@ mod tests {
     fn test_star_two() {
         assert_eq!(star_two(INPUT), 336);
     }
+
+    #[test]
+    fn test_toboggan_simulation() {
+        let mut simulation = toboggan_simulation(INPUT, Slope::new(3, 1).unwrap()).unwrap();
+        assert!(simulation.advance());
+        assert!(simulation.advance());
+
+        assert_eq!(
+            simulation.frame().lines().take(6).collect::<Vec<_>>(),
+            vec![
+                "Row 2/10 at column 6, 1 trees hit",
+                "",
+                "O.##.......",
+                "#..O#...#..",
+                ".#....@..#.",
+                "..#.#...#.#",
+            ]
+        );
+
+        while simulation.advance() {}
+        assert!(simulation
+            .frame()
+            .starts_with("Row 10/10 at column 30, 7 trees hit"));
+
+        assert!(toboggan_simulation("", Slope::new(3, 1).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_toboggan_simulation_leftwards() {
+        let slope = Slope::new(-3, 1).unwrap();
+        let mut simulation = toboggan_simulation(INPUT, slope).unwrap();
+        assert!(simulation.advance());
+
+        assert_eq!(
+            simulation.frame().lines().take(4).collect::<Vec<_>>(),
+            vec![
+                "Row 1/10 at column -3, 1 trees hit",
+                "",
+                "O.##.......",
+                "#...#...@..",
+            ]
+        );
+
+        while simulation.advance() {}
+        let world = INPUT.parse::<World>().unwrap();
+        assert!(simulation.frame().starts_with(&format!(
+            "Row 10/10 at column -30, {} trees hit",
+            world.path(slope).trees
+        )));
+    }
+
+    #[test]
+    fn test_path() {
+        let world = INPUT.parse::<World>().unwrap();
+        let path = world.path("3,1".parse().unwrap());
+
+        assert_eq!(path.trees, 7);
+        assert_eq!(path.visited.len(), 11);
+        assert_eq!(path.visited[4], Vector2::new(12, 4));
+        assert_eq!(world.path(Slope::new(1, 2).unwrap()).visited.len(), 6);
+        assert_eq!(
+            world.path(Slope::new(-3, 1).unwrap()).visited[1],
+            Vector2::new(-3, 1)
+        );
+
+        assert_eq!(
+            world.render_path(&path),
+            "O.##.......
+#..O#...#..
+.#....X..#.
+..#.#...#O#
+.X...##..#.
+..#.X#.....
+.#.#.#.O..#
+.#........X
+#.X#...#...
+#...#X....#
+.#..#...X.#"
+        );
+
+        // Hand built paths can stray off the map
+        let mut path = world.path(Slope::new(1, 1).unwrap());
+        path.visited = vec![Vector2::new(0, -1), Vector2::new(1, 0), Vector2::new(2, 11)];
+        assert!(world.render_path(&path).starts_with(".O##......."));
+    }
+
+    #[test]
+    fn test_fewest_trees() {
+        let world = INPUT.parse::<World>().unwrap();
+        let slopes = vec![
+            Slope::new(1, 1).unwrap(),
+            Slope::new(3, 1).unwrap(),
+            Slope::new(5, 1).unwrap(),
+        ];
+
+        let best = world.fewest_trees(slopes).unwrap();
+        assert_eq!(best.slope, Slope::new(1, 1).unwrap());
+        assert_eq!(best.trees, 2);
+
+        let best = world.fewest_trees(Slope::search_space(3, 2)).unwrap();
+        assert!(Slope::search_space(3, 2).all(|slope| world.path(slope).trees >= best.trees));
+        assert_eq!(Slope::search_space(3, 2).count(), 14);
+        assert_eq!(world.fewest_trees(vec![]), None);
+    }
+
+    #[test]
+    fn test_parse_slope() {
+        assert_eq!("3,1".parse(), Ok(Slope::new(3, 1).unwrap()));
+        assert_eq!(" -1, 2".parse::<Slope>().map(|s| s.right()), Ok(-1));
+        assert_eq!(
+            "3,0".parse::<Slope>(),
+            Err("The toboggan has to go down".to_string())
+        );
+        assert_eq!(
+            "3".parse::<Slope>(),
+            Err("Invalid slope `3`, expected `right,down`".to_string())
+        );
+    }
 }
@@ -1,11 +1,11 @@
-use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
 
-use itertools::Itertools;
-
-use crate::math::Vector2;
+use crate::automaton::{Automaton, Dense, NeighbourGraph, Visibility};
+use crate::cycle::{self, Cycle, Detector};
+use crate::grid::Grid;
+use crate::playback::Simulation;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 enum Location {
@@ -27,9 +27,11 @@ impl TryFrom<char> for Location {
     }
 }
 
-impl Location {
-    fn as_char(&self) -> char {
-        match self {
+const ALL_LOCATIONS: &[Location] = &[Location::Floor, Location::FilledSeat, Location::EmptySeat];
+
+impl From<Location> for char {
+    fn from(l: Location) -> Self {
+        match l {
             Location::Floor => '.',
             Location::FilledSeat => '#',
             Location::EmptySeat => 'L',
@@ -37,172 +39,157 @@ impl Location {
     }
 }
 
-const ALL_DIRECTIONS: &[Vector2<isize>] = &[
-    Vector2::new(-1, 0),  // Left
-    Vector2::new(-1, 1),  // Left-Up
-    Vector2::new(0, 1),   // Up
-    Vector2::new(1, 1),   // Right-Up
-    Vector2::new(1, 0),   // Right
-    Vector2::new(1, -1),  // Right-Down
-    Vector2::new(0, -1),  // Down
-    Vector2::new(-1, -1), // Left-Down
-];
-
-struct SeatMap {
-    seats: Vec<Vec<Location>>,
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Neighbourhood {
+    /// The eight surrounding cells.
+    Adjacent,
+    /// The first seat in each of the eight directions.
+    Visible,
+    /// The first seat in each of the eight directions, at most this many cells away.
+    Range(usize),
 }
 
-impl fmt::Display for SeatMap {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            self.seats
-                .iter()
-                .map(|row| row.iter().map(Location::as_char).collect::<String>())
-                .intersperse("\n".into())
-                .collect::<String>()
-        )
-    }
+/// How seats fill and empty as people move around.
+///
+/// Parses from an optional preset, `part1` or `part2`, followed by overrides, e.g.
+/// `part2 range=3 vacate=4 blocks=L#`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct SeatingRules {
+    neighbourhood: Neighbourhood,
+    /// An empty seat fills when at most this many neighbours are occupied.
+    occupy_threshold: usize,
+    /// An occupied seat empties when at least this many neighbours are occupied.
+    vacate_threshold: usize,
+    /// The cells that stop people looking further in a direction.
+    blocks_sight: Vec<Location>,
 }
 
-impl SeatMap {
-    fn is_out_of_bounds(&self, location: Vector2<isize>) -> bool {
-        location.y() < 0
-            || (location.y() as usize) >= self.seats.len()
-            || location.x() < 0
-            || (location.x() as usize) >= self.seats[location.y() as usize].len()
-    }
-
-    fn adjacent_seats<'a>(&'a self, to: Vector2<isize>) -> impl Iterator<Item = Location> + 'a {
-        ALL_DIRECTIONS.iter().filter_map(move |&dir| {
-            let location = to + dir;
-
-            if !self.is_out_of_bounds(location) {
-                Some(self.seats[location.y() as usize][location.x() as usize])
-            } else {
-                None
-            }
-        })
+impl SeatingRules {
+    fn part_one() -> Self {
+        Self {
+            neighbourhood: Neighbourhood::Adjacent,
+            occupy_threshold: 0,
+            vacate_threshold: 4,
+            blocks_sight: vec![Location::EmptySeat, Location::FilledSeat],
+        }
     }
 
-    fn visible_seats<'a>(&'a self, to: Vector2<isize>) -> impl Iterator<Item = Location> + 'a {
-        ALL_DIRECTIONS.iter().filter_map(move |&dir| {
-            (1..)
-                .map(|offset| {
-                    let location = to + dir * offset;
-                    if self.is_out_of_bounds(location) {
-                        None
-                    } else {
-                        Some(location)
-                    }
-                })
-                .take_while(Option::is_some)
-                .map(Option::unwrap)
-                .find_map(|location| {
-                    match self.seats[location.y() as usize][location.x() as usize] {
-                        l @ Location::FilledSeat => Some(l),
-                        l @ Location::EmptySeat => Some(l),
-                        _ => None,
-                    }
-                })
-        })
+    fn part_two() -> Self {
+        Self {
+            neighbourhood: Neighbourhood::Visible,
+            vacate_threshold: 5,
+            ..Self::part_one()
+        }
     }
 
-    fn all_seats(&self) -> impl Iterator<Item = &Location> {
-        self.seats.iter().flat_map(|row| row.iter())
+    fn visibility(&self) -> Visibility<Location> {
+        let visibility = match self.neighbourhood {
+            Neighbourhood::Adjacent => Visibility::adjacent(),
+            Neighbourhood::Visible => Visibility::unlimited(),
+            Neighbourhood::Range(range) => Visibility::unlimited().max_range(range),
+        };
+
+        ALL_LOCATIONS
+            .iter()
+            .filter(|l| !self.blocks_sight.contains(l))
+            .fold(visibility, |visibility, &l| visibility.see_through(l))
     }
 
-    fn apply_adjacent_rule(&self, to: (Vector2<isize>, Location)) -> Location {
-        let (coord, l) = to;
-
-        if l == Location::Floor {
-            return Location::Floor;
-        }
+    fn apply(&self, l: Location, neighbours: &[Location]) -> Location {
+        let occupied = neighbours
+            .iter()
+            .filter(|&&l| l == Location::FilledSeat)
+            .count();
 
         match l {
-            Location::EmptySeat
-                if self
-                    .adjacent_seats(coord)
-                    .all(|l| l == Location::EmptySeat || l == Location::Floor) =>
-            {
-                Location::FilledSeat
-            }
-            Location::FilledSeat
-                if self
-                    .adjacent_seats(coord)
-                    .filter(|&l| l == Location::FilledSeat)
-                    .count()
-                    >= 4 =>
-            {
-                Location::EmptySeat
-            }
+            Location::EmptySeat if occupied <= self.occupy_threshold => Location::FilledSeat,
+            Location::FilledSeat if occupied >= self.vacate_threshold => Location::EmptySeat,
             s => s,
         }
     }
+}
 
-    fn apply_visible_rule(&self, to: (Vector2<isize>, Location)) -> Location {
-        let (coord, l) = to;
+fn parse_threshold(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|e| format!("Invalid threshold `{}`. {}", value, e))
+}
 
-        if l == Location::Floor {
-            return Location::Floor;
-        }
+impl FromStr for SeatingRules {
+    type Err = String;
 
-        match l {
-            Location::EmptySeat
-                if self
-                    .visible_seats(coord)
-                    .all(|l| l == Location::EmptySeat || l == Location::Floor) =>
-            {
-                Location::FilledSeat
-            }
-            Location::FilledSeat
-                if self
-                    .visible_seats(coord)
-                    .filter(|&l| l == Location::FilledSeat)
-                    .count()
-                    >= 5 =>
-            {
-                Location::EmptySeat
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace().peekable();
+
+        let mut rules = match parts.peek() {
+            Some(&"part2") => SeatingRules::part_two(),
+            _ => SeatingRules::part_one(),
+        };
+        parts.next_if(|&preset| preset == "part1" || preset == "part2");
+
+        for part in parts {
+            match part.split_once('=') {
+                None if part == "adjacent" => rules.neighbourhood = Neighbourhood::Adjacent,
+                None if part == "visible" => rules.neighbourhood = Neighbourhood::Visible,
+                Some(("range", value)) => {
+                    rules.neighbourhood = Neighbourhood::Range(
+                        value
+                            .parse()
+                            .map_err(|e| format!("Invalid range `{}`. {}", value, e))?,
+                    )
+                }
+                Some(("occupy", value)) => rules.occupy_threshold = parse_threshold(value)?,
+                Some(("vacate", value)) => rules.vacate_threshold = parse_threshold(value)?,
+                Some(("blocks", value)) => {
+                    rules.blocks_sight = value
+                        .chars()
+                        .map(Location::try_from)
+                        .collect::<Result<_, _>>()?
+                }
+                _ => return Err(format!("Unknown seating rule `{}`", part)),
             }
-            s => s,
         }
-    }
 
-    fn tick(self, use_adjacent_rule: bool) -> Self {
-        Self {
-            seats: self
-                .seats
-                .iter()
-                .enumerate()
-                .map(|(y, row)| {
-                    row.iter()
-                        .enumerate()
-                        .map(|(x, &l)| {
-                            if use_adjacent_rule {
-                                self.apply_adjacent_rule((Vector2::new(x as isize, y as isize), l))
-                            } else {
-                                self.apply_visible_rule((Vector2::new(x as isize, y as isize), l))
-                            }
-                        })
-                        .collect()
-                })
-                .collect(),
-        }
+        Ok(rules)
     }
+}
+
+struct SeatMap {
+    seats: Grid<Location>,
+}
 
-    fn tick_until_stable(self, use_adjacent_rule: bool) -> Self {
-        let mut observed_states: HashSet<_> = HashSet::new();
-        let mut map = self;
-        observed_states.insert(map.seats.clone());
+impl fmt::Display for SeatMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.seats)
+    }
+}
 
-        loop {
-            map = map.tick(use_adjacent_rule);
+impl SeatMap {
+    fn all_seats(&self) -> impl Iterator<Item = &Location> {
+        self.seats.iter()
+    }
 
-            if !observed_states.insert(map.seats.clone()) {
-                break map;
+    /// The seats once nobody moves any more, or an error if people keep moving around forever.
+    fn tick_until_stable(self, rules: &SeatingRules) -> Result<Self, String> {
+        let graph = rules.visibility().graph(&self.seats);
+        let mut automaton = Dense::new(self.seats, graph, |l, neighbours: &[Location]| {
+            rules.apply(l, neighbours)
+        });
+        let mut detector = Detector::new();
+        detector.observe(cycle::fingerprint(&automaton.grid().cells));
+
+        while automaton.step() {
+            if let Some(cycle) = detector.observe(cycle::fingerprint(&automaton.grid().cells)) {
+                return Err(format!(
+                    "Seats never settle, from round {} they repeat every {} rounds",
+                    cycle.prefix, cycle.period
+                ));
             }
         }
+
+        Ok(Self {
+            seats: automaton.into_grid(),
+        })
     }
 }
 
@@ -210,27 +197,82 @@ impl FromStr for SeatMap {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let seats_or_error: Result<_, Self::Err> = s
-            .lines()
-            .filter_map(|l| {
-                let trimmed = l.trim();
-
-                if trimmed.len() > 0 {
-                    Some(trimmed)
-                } else {
-                    None
-                }
-            })
-            .map(|l| l.chars().map(Location::try_from).collect())
-            .collect();
+        s.parse().map(|seats| Self { seats })
+    }
+}
+
+type BoxedRule = Box<dyn Fn(Location, &[Location]) -> Location>;
+
+/// Seats filling and emptying a round at a time, until nobody moves or the seats are back to
+/// an earlier round.
+pub struct SeatSimulation {
+    automaton: Dense<Location, NeighbourGraph, BoxedRule>,
+    rounds: usize,
+    detector: Detector<u64>,
+    /// Found once the seats are back to an earlier round.
+    cycle: Option<Cycle>,
+}
+
+/// Simulate the seat map `input` under seating rules such as `part2 vacate=4`.
+pub fn seating_simulation(input: &str, rules: &str) -> Result<SeatSimulation, String> {
+    let seats = input.parse::<SeatMap>()?.seats;
+    let rules = rules.parse::<SeatingRules>()?;
+    let graph = rules.visibility().graph(&seats);
+    let rule: BoxedRule = Box::new(move |l, neighbours| rules.apply(l, neighbours));
+    let mut detector = Detector::new();
+    detector.observe(cycle::fingerprint(&seats.cells));
+
+    Ok(SeatSimulation {
+        automaton: Dense::new(seats, graph, rule),
+        rounds: 0,
+        detector,
+        cycle: None,
+    })
+}
 
-        seats_or_error.map(|seats| Self { seats })
+impl Simulation for SeatSimulation {
+    fn frame(&self) -> String {
+        let map = SeatMap {
+            seats: self.automaton.grid().clone(),
+        };
+        let occupied = map
+            .all_seats()
+            .filter(|&&l| l == Location::FilledSeat)
+            .count();
+        let repeating = match self.cycle {
+            Some(cycle) => format!(
+                ", the same as round {}, repeating every {} rounds",
+                cycle.prefix, cycle.period
+            ),
+            None => String::new(),
+        };
+
+        format!(
+            "Round {}, {} seats occupied{}\n\n{}",
+            self.rounds, occupied, repeating, map
+        )
+    }
+
+    fn advance(&mut self) -> bool {
+        // A round where nobody moves isn't shown, a round repeating an earlier one is the last
+        if self.cycle.is_some() || !self.automaton.step() {
+            return false;
+        }
+
+        self.rounds += 1;
+        self.cycle = self
+            .detector
+            .observe(cycle::fingerprint(&self.automaton.grid().cells));
+
+        true
     }
 }
 
 pub fn star_one(input: &str) -> usize {
     let seat_map = input.parse::<SeatMap>().expect("Invalid seat map");
-    let stable_map = seat_map.tick_until_stable(true);
+    let stable_map = seat_map
+        .tick_until_stable(&SeatingRules::part_one())
+        .expect("Seats should settle");
 
     stable_map
         .all_seats()
@@ -240,7 +282,9 @@ pub fn star_one(input: &str) -> usize {
 
 pub fn star_two(input: &str) -> usize {
     let seat_map = input.parse::<SeatMap>().expect("Invalid seat map");
-    let stable_map = seat_map.tick_until_stable(false);
+    let stable_map = seat_map
+        .tick_until_stable(&SeatingRules::part_two())
+        .expect("Seats should settle");
 
     stable_map
         .all_seats()
@@ -250,7 +294,10 @@ pub fn star_two(input: &str) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::{star_one, star_two};
+    use super::{
+        seating_simulation, star_one, star_two, Location, Neighbourhood, SeatMap, SeatingRules,
+    };
+    use crate::playback::Simulation;
     const INPUT: &'static str = "L.LL.LL.LL
 LLLLLLL.LL
 L.L.L..L..
@@ -271,4 +318,83 @@ L.LLLLL.LL";
     fn test_star_two() {
         assert_eq!(star_two(INPUT), 26);
     }
+
+    #[test]
+    fn test_parse_seating_rules() {
+        assert_eq!("part2".parse(), Ok(SeatingRules::part_two()));
+        assert_eq!(
+            "part2 range=3 occupy=1 vacate=4 blocks=#".parse(),
+            Ok(SeatingRules {
+                neighbourhood: Neighbourhood::Range(3),
+                occupy_threshold: 1,
+                vacate_threshold: 4,
+                blocks_sight: vec![Location::FilledSeat],
+            })
+        );
+        assert_eq!(
+            "vacate=x".parse::<SeatingRules>(),
+            Err("Invalid threshold `x`. invalid digit found in string".to_string())
+        );
+        assert!("part3".parse::<SeatingRules>().is_err());
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let count = |rules: &str| {
+            INPUT
+                .parse::<SeatMap>()
+                .unwrap()
+                .tick_until_stable(&rules.parse().unwrap())
+                .unwrap()
+                .all_seats()
+                .filter(|&&l| l == Location::FilledSeat)
+                .count()
+        };
+
+        assert_eq!(count("part1"), 37);
+        assert_eq!(count("part1 blocks=L# visible vacate=5"), 26);
+        // When floor blocks sight too, the first cell in each direction is the adjacent one
+        assert_eq!(count("visible blocks=L#."), count("part1"));
+    }
+
+    #[test]
+    fn test_seating_simulation() {
+        let mut simulation = seating_simulation(INPUT, "part1").unwrap();
+
+        assert!(simulation
+            .frame()
+            .starts_with("Round 0, 0 seats occupied\n\nL.LL.LL.LL\n"));
+        assert!(simulation.advance());
+        assert!(simulation
+            .frame()
+            .starts_with("Round 1, 71 seats occupied\n\n#.##.##.##\n"));
+
+        while simulation.advance() {}
+        assert!(simulation.frame().starts_with("Round 5, 37 seats occupied"));
+
+        assert!(seating_simulation(INPUT, "part3").is_err());
+    }
+
+    #[test]
+    fn test_oscillating_rules() {
+        // Every empty seat fills and every filled seat empties, every round
+        let rules = "occupy=8 vacate=0".parse().unwrap();
+        let result = INPUT.parse::<SeatMap>().unwrap().tick_until_stable(&rules);
+        assert_eq!(
+            result.err(),
+            Some("Seats never settle, from round 0 they repeat every 2 rounds".to_string())
+        );
+
+        let mut simulation = seating_simulation(INPUT, "occupy=8 vacate=0").unwrap();
+        assert!(simulation.advance());
+        assert!(simulation
+            .frame()
+            .starts_with("Round 1, 71 seats occupied\n\n"));
+        assert!(simulation.advance());
+        assert!(simulation.frame().starts_with(
+            "Round 2, 0 seats occupied, the same as round 0, repeating every 2 rounds\n\n"
+        ));
+        assert!(!simulation.advance());
+        assert!(simulation.frame().starts_with("Round 2, "));
+    }
 }
@@ -1,8 +1,10 @@
 use core::f64::consts::PI;
+use std::collections::HashSet;
 use std::str::FromStr;
 
 use crate::math::Vector2;
 use crate::parse_lines;
+use crate::playback::Simulation;
 
 const NORTH_VECTOR: Vector2<isize> = Vector2::new(0, 1);
 const SOUTH_VECTOR: Vector2<isize> = Vector2::new(0, -1);
@@ -147,6 +149,117 @@ impl World {
     }
 }
 
+/// The width and height of the area around the ship shown in a frame.
+const VIEW: (isize, isize) = (41, 21);
+
+/// The cells on a straight line from `from` to `to`, excluding `from`.
+fn line(from: Vector2<isize>, to: Vector2<isize>) -> impl Iterator<Item = Vector2<isize>> {
+    let (dx, dy) = (to.x() - from.x(), to.y() - from.y());
+    let steps = dx.abs().max(dy.abs());
+    // Rounds to the nearest cell, so diagonal lines don't lean to one side
+    let at = move |d: isize, i: isize| (2 * d * i + steps).div_euclid(2 * steps);
+
+    (1..=steps).map(move |i| from + Vector2::new(at(dx, i), at(dy, i)))
+}
+
+/// The ship sailing an instruction at a time, optionally steered by a waypoint.
+pub struct ShipSimulation {
+    instructions: Vec<Instruction>,
+    executed: usize,
+    use_waypoint: bool,
+    position: Vector2<isize>,
+    /// The direction of the ship or, when steering by waypoint, the waypoint.
+    heading: Vector2<isize>,
+    /// Every cell the ship has sailed through.
+    trail: HashSet<Vector2<isize>>,
+}
+
+pub fn ship_simulation(input: &str, use_waypoint: bool) -> Result<ShipSimulation, String> {
+    let instructions = input
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()?;
+
+    Ok(ShipSimulation {
+        instructions,
+        executed: 0,
+        use_waypoint,
+        position: Vector2::new(0, 0),
+        heading: if use_waypoint {
+            Vector2::new(10, 1)
+        } else {
+            Vector2::new(1, 0)
+        },
+        trail: vec![Vector2::new(0, 0)].into_iter().collect(),
+    })
+}
+
+impl Simulation for ShipSimulation {
+    fn frame(&self) -> String {
+        let last = match self.executed {
+            0 => "start".to_string(),
+            n => format!("{:?}", self.instructions[n - 1]),
+        };
+        let header = format!(
+            "Instruction {}/{} ({}), ship at ({}, {}) heading ({}, {}), distance {}",
+            self.executed,
+            self.instructions.len(),
+            last,
+            self.position.x(),
+            self.position.y(),
+            self.heading.x(),
+            self.heading.y(),
+            self.position.manhattan_distance(Vector2::new(0, 0))
+        );
+
+        // North is up, so rows go from high to low y
+        let (half_width, half_height) = (VIEW.0 / 2, VIEW.1 / 2);
+        let view = (-half_height..=half_height)
+            .rev()
+            .map(|dy| {
+                (-half_width..=half_width)
+                    .map(|dx| {
+                        let at = self.position + Vector2::new(dx, dy);
+
+                        if at == self.position {
+                            'S'
+                        } else if self.trail.contains(&at) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!("{}\n\n{}", header, view)
+    }
+
+    fn advance(&mut self) -> bool {
+        let instruction = match self.instructions.get(self.executed) {
+            Some(instruction) => instruction,
+            None => return false,
+        };
+
+        let (position, heading) = if self.use_waypoint {
+            World::execute_instruction_part_2(instruction, self.position, self.heading)
+        } else {
+            World::execute_instruction_part_1(instruction, self.position, self.heading)
+        };
+
+        self.trail.extend(line(self.position, position));
+        self.position = position;
+        self.heading = heading;
+        self.executed += 1;
+
+        true
+    }
+}
+
 pub fn star_one(input: &str) -> isize {
     let instructions = parse_lines::<Instruction>(input).collect::<Vec<_>>();
     let mut world = World::new();
@@ -167,7 +280,9 @@ pub fn star_two(input: &str) -> isize {
 
 #[cfg(test)]
 mod tests {
-    use super::{star_one, star_two};
+    use super::{ship_simulation, star_one, star_two};
+    use crate::math::Vector2;
+    use crate::playback::Simulation;
     const INPUT: &'static str = "F10
 N3
 F7
@@ -183,4 +298,38 @@ F11";
     fn test_star_two() {
         assert_eq!(star_two(INPUT), 286);
     }
+
+    #[test]
+    fn test_ship_simulation() {
+        let mut simulation = ship_simulation(INPUT, true).unwrap();
+        while simulation.advance() {}
+
+        let frame = simulation.frame();
+        assert!(frame.starts_with(
+            "Instruction 5/5 (Forward(11)), ship at (214, -72) heading (4, -10), distance 286"
+        ));
+        // The ship is in the middle of the view
+        assert_eq!(
+            frame.lines().nth(2 + 10).unwrap().chars().nth(20),
+            Some('S')
+        );
+
+        assert!(ship_simulation("X1", false).is_err());
+    }
+
+    #[test]
+    fn test_ship_simulation_trail() {
+        let mut simulation = ship_simulation(INPUT, false).unwrap();
+        simulation.advance();
+        assert_eq!(simulation.trail.len(), 11);
+        assert!((0..=10).all(|x| simulation.trail.contains(&Vector2::new(x, 0))));
+
+        // Steering by waypoint sails diagonally
+        let mut simulation = ship_simulation(INPUT, true).unwrap();
+        simulation.advance();
+        assert_eq!(simulation.position, Vector2::new(100, 10));
+        assert_eq!(simulation.trail.len(), 101);
+        assert!(simulation.trail.contains(&Vector2::new(50, 5)));
+        assert!(simulation.trail.contains(&Vector2::new(100, 10)));
+    }
 }
@@ -1,184 +1,296 @@
-use std::collections::{HashMap, HashSet};
-use std::hash::Hash;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
 use std::str::FromStr;
 
-use itertools::Itertools;
+use crate::automaton::{Automaton, SparseSet};
+use crate::grid::Grid;
+use crate::playback::Simulation;
+use crate::render;
 
-use crate::math::{Vector3, Vector4};
+/// A location in `N` dimensions, the first two being x and y.
+type Point<const N: usize> = [isize; N];
 
-lazy_static! {
-    static ref NEIGHBOURS_3: Vec<Vector3<isize>> = [[0, 1, -1], [0, 1, -1], [0, 1, -1]]
-        .iter()
-        .multi_cartesian_product()
-        .filter_map(|v| if v[..] == [&0, &0, &0][..] {
-            None
-        } else {
-            Some(v)
-        })
-        .map(|v| Vector3::new(*v[0], *v[1], *v[2]))
-        .collect();
-    static ref NEIGHBOURS_4: Vec<Vector4<isize>> = [[0, 1, -1], [0, 1, -1], [0, 1, -1], [0, 1, -1]]
-        .iter()
-        .multi_cartesian_product()
-        .filter_map(|v| if v[..] == [&0, &0, &0, &0][..] {
-            None
-        } else {
-            Some(v)
+/// Every offset to a neighbouring location in `N` dimensions, `3^N - 1` of them.
+fn neighbour_offsets<const N: usize>() -> Vec<Point<N>> {
+    let count = 3_usize.pow(N as u32);
+
+    (0..count)
+        .map(|i| {
+            let mut offset = [0; N];
+            let mut rest = i;
+
+            for o in offset.iter_mut() {
+                *o = (rest % 3) as isize - 1;
+                rest /= 3;
+            }
+
+            offset
         })
-        .map(|v| Vector4::new(*v[0], *v[1], *v[2], *v[3]))
-        .collect();
+        .filter(|offset| offset.iter().any(|&o| o != 0))
+        .collect()
 }
-trait NDimVector: Hash + Eq + PartialEq + Copy + Clone {
-    fn new_ndim(x: isize, y: isize) -> Self;
-    fn neighbours(self) -> Box<dyn Iterator<Item = Self>>;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Cube {
+    Active,
+    Inactive,
 }
 
-impl NDimVector for Vector3<isize> {
-    fn new_ndim(x: isize, y: isize) -> Self {
-        Self::new(x, y, 0)
-    }
+impl TryFrom<char> for Cube {
+    type Error = String;
 
-    fn neighbours(self) -> Box<dyn Iterator<Item = Self>> {
-        Box::new(NEIGHBOURS_3.iter().map(move |&dir| self + dir))
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            '#' => Ok(Self::Active),
+            '.' => Ok(Self::Inactive),
+            _ => Err(format!("Invalid location `{}`", c)),
+        }
     }
 }
 
-impl NDimVector for Vector4<isize> {
-    fn new_ndim(x: isize, y: isize) -> Self {
-        Self::new(x, y, 0, 0)
+fn conway_rule(active: bool, active_neighbours: usize) -> bool {
+    matches!((active, active_neighbours), (true, 2) | (_, 3))
+}
+
+type Neighbourhood<const N: usize> = Box<dyn Fn(Point<N>, &mut Vec<Point<N>>)>;
+type Rule = fn(bool, usize) -> bool;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Mode {
+    /// Simulate every cube.
+    BruteForce,
+    /// Every dimension past x and y starts at 0 and evolves symmetrically around it, so only
+    /// simulate the cubes with non-negative coordinates in those dimensions. A cube stands for
+    /// all of its mirror images, each of which counts towards the cubes next to it.
+    Symmetric,
+}
+
+fn add<const N: usize>(mut location: Point<N>, offset: &Point<N>) -> Point<N> {
+    for (c, o) in location.iter_mut().zip(offset) {
+        *c += o;
     }
 
-    fn neighbours(self) -> Box<dyn Iterator<Item = Self>> {
-        Box::new(NEIGHBOURS_4.iter().map(move |&dir| self + dir))
+    location
+}
+
+/// Mirror `location` into the non-negative orthant of the dimensions past x and y.
+fn canonical<const N: usize>(mut location: Point<N>) -> Point<N> {
+    for c in location.iter_mut().skip(2) {
+        *c = c.abs();
     }
+
+    location
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-enum State {
-    Active,
-    Inactive,
+/// The number of cubes a canonical location stands for, one for every combination of mirrored
+/// non-zero coordinates.
+fn multiplicity<const N: usize>(location: &Point<N>) -> usize {
+    1 << location.iter().skip(2).filter(|&&c| c != 0).count()
 }
-struct World<Vector> {
-    locations: HashMap<Vector, State>,
+
+/// A pocket dimension of Conway cubes with `N` dimensions.
+struct World<const N: usize> {
+    automaton: SparseSet<Point<N>, Neighbourhood<N>, Rule>,
+    mode: Mode,
 }
 
-impl<Vector: NDimVector> World<Vector> {
+impl<const N: usize> World<N> {
+    fn new(active: Vec<Point<N>>, mode: Mode) -> Self {
+        let offsets = neighbour_offsets::<N>();
+        let neighbourhood: Neighbourhood<N> = Box::new(move |location, out| match mode {
+            Mode::BruteForce => out.extend(offsets.iter().map(|offset| add(location, offset))),
+            Mode::Symmetric => {
+                for image in mirror_images(location) {
+                    out.extend(
+                        offsets
+                            .iter()
+                            .map(|offset| add(image, offset))
+                            .filter(|&neighbour| canonical(neighbour) == neighbour),
+                    );
+                }
+            }
+        });
+
+        Self {
+            automaton: SparseSet::new(active, neighbourhood, conway_rule),
+            mode,
+        }
+    }
+
+    /// The same world, simulated in `mode` from now on.
+    ///
+    /// **Note:** Only switch to [`Mode::Symmetric`] while the world is still symmetric, e.g.
+    /// right after parsing.
+    fn with_mode(self, mode: Mode) -> Self {
+        let active = self.automaton.live().iter().copied();
+
+        let active = match (self.mode, mode) {
+            (Mode::BruteForce, Mode::Symmetric) => active
+                .filter(|&location| canonical(location) == location)
+                .collect(),
+            (Mode::Symmetric, Mode::BruteForce) => active.flat_map(mirror_images).collect(),
+            _ => active.collect(),
+        };
+
+        Self::new(active, mode)
+    }
+
     fn active_cubes(&self) -> usize {
-        self.locations
-            .values()
-            .filter(|&s| s == &State::Active)
-            .count()
-    }
-
-    fn neighbours<'a>(
-        locations: &'a HashMap<Vector, State>,
-        location: Vector,
-    ) -> Box<dyn Iterator<Item = State> + 'a> {
-        Box::new(
-            location
-                .neighbours()
-                .filter_map(move |neighbour_location| locations.get(&neighbour_location).cloned()),
-        )
+        match self.mode {
+            Mode::BruteForce => self.automaton.live().len(),
+            Mode::Symmetric => self.automaton.live().iter().map(multiplicity).sum(),
+        }
     }
 
-    fn tick(self) -> Self {
-        let locations = self.locations;
+    fn tick(mut self) -> Self {
+        self.automaton.step();
 
-        let new_locations: HashSet<Vector> = locations
-            .iter()
-            .flat_map(|(&l, _)| l.neighbours())
-            .collect();
+        self
+    }
+}
 
-        let new_state = new_locations
-            .into_iter()
-            .map(|l| {
-                let neighbours = World::neighbours(&locations, l);
-
-                match locations.get(&l) {
-                    Some(State::Active) => {
-                        let active_neighbours = neighbours.filter(|s| *s == State::Active).count();
-
-                        if active_neighbours == 2 || active_neighbours == 3 {
-                            (l, State::Active)
-                        } else {
-                            (l, State::Inactive)
-                        }
-                    }
-                    _ => {
-                        let active_neighbours = neighbours.filter(|s| *s == State::Active).count();
-
-                        if active_neighbours == 3 {
-                            (l, State::Active)
-                        } else {
-                            (l, State::Inactive)
-                        }
-                    }
-                }
-            })
-            .collect();
+impl<const N: usize> fmt::Display for World<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let live = self.automaton.live();
 
-        Self {
-            locations: new_state,
+        match self.mode {
+            Mode::BruteForce => write!(f, "{}", render::render_slices(live)),
+            Mode::Symmetric => {
+                let all: HashSet<_> = live.iter().copied().flat_map(mirror_images).collect();
+
+                write!(f, "{}", render::render_slices(&all))
+            }
         }
     }
 }
 
-impl<Vector: NDimVector> FromStr for World<Vector> {
+impl<const N: usize> FromStr for World<N> {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let locations = s
-            .lines()
-            .filter_map(|l| {
-                let trimmed = l.trim();
-
-                if trimmed.len() > 0 {
-                    Some(trimmed)
-                } else {
-                    None
-                }
-            })
-            .enumerate()
-            .flat_map(move |(y, l)| {
-                l.chars().enumerate().map(move |(x, c)| match c {
-                    '#' => Ok((Vector::new_ndim(x as isize, y as isize), State::Active)),
-                    '.' => Ok((Vector::new_ndim(x as isize, y as isize), State::Inactive)),
-                    _ => Err(format!("Invalid location `{}`", c)),
-                })
+        if N < 2 {
+            return Err(format!("Expected at least 2 dimensions, got {}", N));
+        }
+
+        let grid = s.parse::<Grid<Cube>>()?;
+        let active = grid
+            .cells()
+            .filter(|&(_, &c)| c == Cube::Active)
+            .map(|(at, _)| {
+                let mut location = [0; N];
+                location[0] = at.x();
+                location[1] = at.y();
+
+                location
             })
-            .collect::<Result<HashMap<_, _>, _>>()?;
+            .collect();
 
-        Ok(Self { locations })
+        Ok(Self::new(active, Mode::BruteForce))
     }
 }
 
-pub fn star_one(input: &str) -> usize {
+/// Every location that mirrors onto the canonical `location`.
+fn mirror_images<const N: usize>(location: Point<N>) -> impl Iterator<Item = Point<N>> {
+    (0..multiplicity(&location)).map(move |signs| {
+        let mut image = location;
+
+        for (sign, c) in image.iter_mut().skip(2).filter(|c| **c != 0).enumerate() {
+            if signs & (1 << sign) != 0 {
+                *c = -*c;
+            }
+        }
+
+        image
+    })
+}
+
+/// The number of active cubes after `cycles` cycles in `N` dimensions.
+fn active_after<const N: usize>(input: &str, cycles: usize, mode: Mode) -> usize {
     let mut world = input
-        .parse::<World<Vector3<isize>>>()
-        .expect("Failed to parse input");
+        .parse::<World<N>>()
+        .expect("Failed to parse input")
+        .with_mode(mode);
 
-    for _ in 0..6 {
+    for _ in 0..cycles {
         world = world.tick()
     }
 
     world.active_cubes()
 }
 
-pub fn star_two(input: &str) -> usize {
-    let mut world = input
-        .parse::<World<Vector4<isize>>>()
-        .expect("Failed to parse input");
+/// Conway cubes booting up a cycle at a time.
+pub struct CubeSimulation<const N: usize> {
+    world: World<N>,
+    cycle: usize,
+    cycles: usize,
+}
 
-    for _ in 0..6 {
-        world = world.tick()
+impl<const N: usize> Simulation for CubeSimulation<N> {
+    fn frame(&self) -> String {
+        format!(
+            "Cycle {}, {} active cubes\n\n{}",
+            self.cycle,
+            self.world.active_cubes(),
+            self.world
+        )
     }
 
-    world.active_cubes()
+    fn advance(&mut self) -> bool {
+        if self.cycle >= self.cycles {
+            return false;
+        }
+
+        self.world.automaton.step();
+        self.cycle += 1;
+
+        true
+    }
+}
+
+fn simulate<const N: usize>(input: &str, cycles: usize) -> Result<Box<dyn Simulation>, String> {
+    let world = input.parse::<World<N>>()?.with_mode(Mode::Symmetric);
+
+    Ok(Box::new(CubeSimulation {
+        world,
+        cycle: 0,
+        cycles,
+    }))
+}
+
+/// Simulate `cycles` cycles of the cubes in `input` in 2 to 6 dimensions.
+pub fn cube_simulation(
+    input: &str,
+    dimensions: usize,
+    cycles: usize,
+) -> Result<Box<dyn Simulation>, String> {
+    match dimensions {
+        2 => simulate::<2>(input, cycles),
+        3 => simulate::<3>(input, cycles),
+        4 => simulate::<4>(input, cycles),
+        5 => simulate::<5>(input, cycles),
+        6 => simulate::<6>(input, cycles),
+        _ => Err(format!(
+            "Can only simulate 2 to 6 dimensions, got {}",
+            dimensions
+        )),
+    }
+}
+
+pub fn star_one(input: &str) -> usize {
+    active_after::<3>(input, 6, Mode::Symmetric)
+}
+
+pub fn star_two(input: &str) -> usize {
+    active_after::<4>(input, 6, Mode::Symmetric)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{star_one, star_two};
+    use super::{
+        active_after, canonical, cube_simulation, mirror_images, neighbour_offsets, star_one,
+        star_two, Mode, World,
+    };
     const INPUT: &'static str = ".#.
 ..#
 ###";
@@ -192,4 +304,114 @@ mod tests {
     fn test_star_two() {
         assert_eq!(star_two(INPUT), 848);
     }
+
+    #[test]
+    fn test_neighbour_offsets() {
+        assert_eq!(neighbour_offsets::<1>(), vec![[-1], [1]]);
+        assert_eq!(neighbour_offsets::<3>().len(), 26);
+        assert_eq!(neighbour_offsets::<4>().len(), 80);
+    }
+
+    #[test]
+    fn test_other_dimensions() {
+        // In 2D the example is a glider, which keeps its five cells
+        assert_eq!(active_after::<2>(INPUT, 6, Mode::BruteForce), 5);
+        assert_eq!(active_after::<3>(INPUT, 1, Mode::BruteForce), 11);
+        assert_eq!(active_after::<4>(INPUT, 1, Mode::BruteForce), 29);
+        assert_eq!(active_after::<5>(INPUT, 6, Mode::Symmetric), 5760);
+    }
+
+    #[test]
+    fn test_symmetric_matches_brute_force() {
+        fn check<const N: usize>(cycles: usize) {
+            for cycle in 0..=cycles {
+                assert_eq!(
+                    active_after::<N>(INPUT, cycle, Mode::Symmetric),
+                    active_after::<N>(INPUT, cycle, Mode::BruteForce),
+                    "{} dimensions after {} cycles",
+                    N,
+                    cycle
+                );
+            }
+        }
+
+        check::<2>(6);
+        check::<3>(6);
+        check::<4>(4);
+        check::<5>(2);
+    }
+
+    #[test]
+    fn test_mode_round_trip() {
+        let mut world = INPUT
+            .parse::<World<4>>()
+            .unwrap()
+            .with_mode(Mode::Symmetric);
+        for _ in 0..3 {
+            world = world.tick();
+        }
+        let symmetric = world.active_cubes();
+
+        let world = world.with_mode(Mode::BruteForce);
+        assert_eq!(world.active_cubes(), symmetric);
+        assert!(world
+            .automaton
+            .live()
+            .iter()
+            .any(|&location| canonical(location) != location));
+    }
+
+    #[test]
+    fn test_mirror_images() {
+        let mut images: Vec<_> = mirror_images([1, 2, 0, 3]).collect();
+        images.sort_unstable();
+
+        assert_eq!(images, vec![[1, 2, 0, -3], [1, 2, 0, 3]]);
+        assert_eq!(mirror_images([5, 5, 1, 1, 1]).count(), 8);
+    }
+
+    #[test]
+    fn test_display() {
+        let world = INPUT.parse::<World<3>>().unwrap();
+        assert_eq!(world.to_string(), "z=0\n.#.\n..#\n###");
+
+        let expected = "z=-1
+#..
+..#
+.#.
+
+z=0
+#.#
+.##
+.#.
+
+z=1
+#..
+..#
+.#.";
+        assert_eq!(world.tick().to_string(), expected);
+
+        let symmetric = INPUT
+            .parse::<World<3>>()
+            .unwrap()
+            .with_mode(Mode::Symmetric);
+        assert_eq!(symmetric.tick().to_string(), expected);
+    }
+
+    #[test]
+    fn test_cube_simulation() {
+        let mut simulation = cube_simulation(INPUT, 3, 1).unwrap();
+
+        assert_eq!(
+            simulation.frame(),
+            "Cycle 0, 5 active cubes\n\nz=0\n.#.\n..#\n###"
+        );
+        assert!(simulation.advance());
+        assert!(simulation
+            .frame()
+            .starts_with("Cycle 1, 11 active cubes\n\nz=-1\n"));
+        assert!(!simulation.advance());
+
+        assert!(cube_simulation(INPUT, 7, 1).is_err());
+    }
 }
@@ -1,179 +1,339 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
 use crate::parser_combinators::*;
 
-pub fn star_one(input: &str) -> isize {
-    // expr   <- term (('*' / '+') term)*
-    // term   <- (number / '(' expr ')')
-    // number <- [0-9]+
-
-    fn term(input: &str) -> ParserResult<&str, isize> {
-        fn sub_expression(input: &str) -> ParserResult<&str, isize> {
-            let (rest, _) = match_char('(')(input)?;
-            let (rest, sub_expression) = {
-                let mut l = 0;
-
-                take_while(move |s| {
-                    if s == "(" {
-                        l += 1;
-                        true
-                    } else if s == ")" {
-                        if l == 0 {
-                            false
-                        } else {
-                            l -= 1;
-                            true
-                        }
-                    } else {
-                        true
-                    }
-                })(rest)?
-            };
-            let (_, expression) = expr(sub_expression)?;
-            let (rest, _) = match_char(')')(rest)?;
-
-            Ok((rest, expression))
+/// An infix operator, how it's written and how it's evaluated. Evaluation fails on overflow.
+#[derive(Debug, Copy, Clone)]
+pub struct BinaryOp {
+    pub symbol: &'static str,
+    pub apply: fn(isize, isize) -> Option<isize>,
+}
+
+/// A prefix operator, how it's written and how it's evaluated. Evaluation fails on overflow.
+#[derive(Debug, Copy, Clone)]
+pub struct UnaryOp {
+    pub symbol: &'static str,
+    pub apply: fn(isize) -> Option<isize>,
+}
+
+// Operators are the same when they're written the same, comparing functions isn't reliable
+impl PartialEq for BinaryOp {
+    fn eq(&self, other: &Self) -> bool {
+        self.symbol == other.symbol
+    }
+}
+
+impl Eq for BinaryOp {}
+
+impl PartialEq for UnaryOp {
+    fn eq(&self, other: &Self) -> bool {
+        self.symbol == other.symbol
+    }
+}
+
+impl Eq for UnaryOp {}
+
+pub const ADD: BinaryOp = BinaryOp {
+    symbol: "+",
+    apply: isize::checked_add,
+};
+
+pub const MULTIPLY: BinaryOp = BinaryOp {
+    symbol: "*",
+    apply: isize::checked_mul,
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Expr {
+    Number(isize),
+    Variable(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn unary(op: UnaryOp, operand: Expr) -> Self {
+        Self::Unary(op, Box::new(operand))
+    }
+
+    pub fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Self {
+        Self::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    /// Evaluate the expression with variables bound by `env`.
+    ///
+    /// Fails on unbound variables and on overflow.
+    pub fn evaluate(&self, env: &HashMap<String, isize>) -> Result<isize, String> {
+        let overflow = || format!("Overflow evaluating `{}`", self);
+
+        match self {
+            Expr::Number(n) => Ok(*n),
+            Expr::Variable(name) => env
+                .get(name)
+                .copied()
+                .ok_or_else(|| format!("Unbound variable `{}`", name)),
+            Expr::Unary(op, operand) => (op.apply)(operand.evaluate(env)?).ok_or_else(overflow),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(env)?, rhs.evaluate(env)?);
+
+                (op.apply)(lhs, rhs).ok_or_else(overflow)
+            }
         }
+    }
+}
 
-        fn number_or_sub_expression(input: &str) -> ParserResult<&str, isize> {
-            one_of_2(integer, sub_expression)(input)
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Variable(name) => write!(f, "{}", name),
+            Expr::Unary(op, operand) => write!(f, "({}{})", op.symbol, operand),
+            Expr::Binary(op, lhs, rhs) => write!(f, "({} {} {})", lhs, op.symbol, rhs),
         }
+    }
+}
+
+/// The operator precedence rules of the "new math".
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Precedence {
+    /// `+` and `*` have equal precedence and are evaluated left to right.
+    Equal,
+    /// `+` is evaluated before `*`.
+    AdditionFirst,
+}
+
+impl Precedence {
+    pub const ALL: [Precedence; 2] = [Precedence::Equal, Precedence::AdditionFirst];
 
-        number_or_sub_expression(input)
+    fn table(self) -> OperatorTable<Expr> {
+        let addition_precedence = match self {
+            Precedence::Equal => 1,
+            Precedence::AdditionFirst => 2,
+        };
+
+        OperatorTable::new()
+            .binary(
+                ADD.symbol,
+                addition_precedence,
+                Associativity::Left,
+                |a, b| Expr::binary(ADD, a, b),
+            )
+            .binary(MULTIPLY.symbol, 1, Associativity::Left, |a, b| {
+                Expr::binary(MULTIPLY, a, b)
+            })
     }
 
-    fn expr(input: &str) -> ParserResult<&str, isize> {
-        let (rest, first) = term(input)?;
+    /// Parse all of `input` as a single expression.
+    pub fn parse(self, input: &str) -> Result<Expr, ParseError> {
+        let table = self.table();
+        let mut parser = expression(&table, atom);
+
+        match parser(input) {
+            Ok((rest, expr)) => {
+                let rest = rest.trim_start();
 
-        fn expressions(input: &str) -> ParserResult<&str, (isize, Option<String>)> {
-            let (rest, op) = one_of_2(match_char('*'), match_char('+'))(input)?;
-            let (rest, v) = term(rest)?;
+                eof(rest).map(|_| expr).map_err(|message| ParseError {
+                    offset: input.len() - rest.len(),
+                    message,
+                })
+            }
+            Err(message) => {
+                // Parse again with tracing enabled to find out how far the parser got
+                let (_, trace) = with_tracing(input, || parser(input));
 
-            Ok((rest, (v, Some(op.to_string()))))
+                Err(trace
+                    .furthest_failure()
+                    .map(|(offset, message)| ParseError {
+                        offset,
+                        message: message.to_string(),
+                    })
+                    .unwrap_or(ParseError { offset: 0, message }))
+            }
         }
+    }
 
-        let (rest, results) = many0(expressions)(rest)?;
-
-        Ok((
-            rest,
-            std::iter::once((first, None))
-                .chain(results.into_iter())
-                .fold(0, |acc, (v, op)| match op {
-                    Some(op) => match op.as_ref() {
-                        "+" => acc + v,
-                        "*" => acc * v,
-                        _ => panic!("Invalid op `{}`", op),
-                    },
-                    None => v,
-                }),
-        ))
-    };
+    /// Parse all of `input` like [`Precedence::parse`], but with a left recursive grammar and a
+    /// packrat parser. Errors in the middle of `input` are reported at offset 0.
+    pub fn parse_left_recursive(self, input: &str) -> Result<Expr, ParseError> {
+        let grammar = Grammar {
+            precedence: self,
+            cache: Packrat::new(),
+        };
+        let (rest, expr) = grammar
+            .expr(input)
+            .map_err(|message| ParseError { offset: 0, message })?;
+        let rest = rest.trim_start();
 
-    input
-        .lines()
-        .filter_map(|l| {
-            let trimmed = l.trim();
+        eof(rest).map(|_| expr).map_err(|message| ParseError {
+            offset: input.len() - rest.len(),
+            message,
+        })
+    }
+}
 
-            if trimmed.len() > 0 {
-                Some(trimmed)
-            } else {
-                None
+/// The new math as a left recursive grammar, e.g. `sum <- sum '+' primary / primary`, parsed
+/// with packrat memoisation rather than precedence climbing.
+struct Grammar {
+    precedence: Precedence,
+    cache: Packrat<Expr>,
+}
+
+impl Grammar {
+    fn expr<'a>(&self, input: &'a str) -> ParserResult<&'a str, Expr> {
+        match self.precedence {
+            // expr <- expr ('+' / '*') primary / primary
+            Precedence::Equal => self.chain("expr", &[ADD, MULTIPLY], input, &|i| self.primary(i)),
+            // product <- product '*' sum / sum
+            Precedence::AdditionFirst => {
+                self.chain("product", &[MULTIPLY], input, &|i| self.sum(i))
             }
-        })
-        .map(|line| {
-            let stripped: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+        }
+    }
 
-            expr(&stripped)
-                .expect("Should be able to parse all expressions")
-                .1
-        })
-        .sum()
+    // sum <- sum '+' primary / primary
+    fn sum<'a>(&self, input: &'a str) -> ParserResult<&'a str, Expr> {
+        self.chain("sum", &[ADD], input, &|i| self.primary(i))
+    }
+
+    /// `rule <- rule op operand / operand` for any `op` of `ops`.
+    fn chain<'a>(
+        &self,
+        rule: &'static str,
+        ops: &[BinaryOp],
+        input: &'a str,
+        operand: &dyn Fn(&'a str) -> ParserResult<&'a str, Expr>,
+    ) -> ParserResult<&'a str, Expr> {
+        left_recursive(&self.cache, rule, |input: &'a str| {
+            self.chain(rule, ops, input, operand)
+                .and_then(|(rest, lhs)| {
+                    let (rest, _) = skip_whitespace0(rest)?;
+                    let op = ops
+                        .iter()
+                        .find(|op| rest.starts_with(op.symbol))
+                        .ok_or_else(|| format!("Expected an operator in `{}`", rest))?;
+                    let (rest, rhs) = operand(&rest[op.symbol.len()..])?;
+
+                    Ok((rest, Expr::binary(*op, lhs, rhs)))
+                })
+                .or_else(|_| operand(input))
+        })(input)
+    }
+
+    // primary <- '(' expr ')' / number / variable
+    fn primary<'a>(&self, input: &'a str) -> ParserResult<&'a str, Expr> {
+        memoize(&self.cache, "primary", |input: &'a str| {
+            let (rest, _) = skip_whitespace0(input)?;
+
+            if let Ok((rest, _)) = match_char('(')(rest) {
+                let (rest, expr) = self.expr(rest)?;
+                let (rest, _) = skip_whitespace0(rest)?;
+                let (rest, _) = match_char(')')(rest)?;
+
+                return Ok((rest, expr));
+            }
+
+            atom(rest)
+        })(input)
+    }
 }
 
-pub fn star_two(input: &str) -> isize {
-    // expr   <- term ('*' term)*
-    // term   <- (number / '(' expr ')') ('+' number / '(' expr ')')*
-    // number <- [0-9]+
-
-    fn term(input: &str) -> ParserResult<&str, isize> {
-        fn sub_expression(input: &str) -> ParserResult<&str, isize> {
-            let (rest, _) = match_char('(')(input)?;
-            let (rest, sub_expression) = {
-                let mut l = 0;
-
-                take_while(move |s| {
-                    if s == "(" {
-                        l += 1;
-                        true
-                    } else if s == ")" {
-                        if l == 0 {
-                            false
-                        } else {
-                            l -= 1;
-                            true
-                        }
-                    } else {
-                        true
-                    }
-                })(rest)?
-            };
-            let (_, expression) = expr(sub_expression)?;
-            let (rest, _) = match_char(')')(rest)?;
-
-            Ok((rest, expression))
+impl fmt::Display for Precedence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Precedence::Equal => write!(f, "equal"),
+            Precedence::AdditionFirst => write!(f, "addition"),
         }
+    }
+}
 
-        fn number_or_sub_expression(input: &str) -> ParserResult<&str, isize> {
-            one_of_2(integer, sub_expression)(input)
+impl FromStr for Precedence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "equal" => Ok(Precedence::Equal),
+            "addition" => Ok(Precedence::AdditionFirst),
+            _ => Err(format!("Invalid precedence `{}`", s)),
         }
-        let (rest, first) = number_or_sub_expression(input)?;
+    }
+}
+
+/// An expression that could not be parsed, `offset` is the byte offset of the problem.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub message: String,
+}
 
-        let (rest, results) = many0(move |input: &str| -> ParserResult<&str, isize> {
-            let (rest, _) = match_char('+')(input)?;
-            number_or_sub_expression(rest)
-        })(rest)?;
+impl ParseError {
+    /// The number of characters of `input` before the problem, e.g. to point at it in a
+    /// terminal.
+    pub fn column(&self, input: &str) -> usize {
+        input
+            .char_indices()
+            .take_while(|&(i, _)| i < self.offset)
+            .count()
+    }
+}
 
-        Ok((
-            rest,
-            std::iter::once(first).chain(results.into_iter()).sum(),
-        ))
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
     }
+}
 
-    fn expr(input: &str) -> ParserResult<&str, isize> {
-        let (rest, first) = term(input)?;
+fn variable(input: &str) -> ParserResult<&str, Expr> {
+    let (rest, name) = take_while(|s| s.chars().all(|c| c.is_ascii_alphabetic()))(input)?;
 
-        let (rest, results) = many0(move |input: &str| -> ParserResult<&str, isize> {
-            let (rest, _) = match_char('*')(input)?;
-            term(rest)
-        })(rest)?;
+    if name.is_empty() {
+        Err(format!("Expected variable in `{}`", input))
+    } else {
+        Ok((rest, Expr::Variable(name.to_string())))
+    }
+}
+
+fn atom(input: &str) -> ParserResult<&str, Expr> {
+    one_of_2(enrich(integer, Expr::Number), variable)(input)
+}
 
-        Ok((
-            rest,
-            std::iter::once(first).chain(results.into_iter()).product(),
-        ))
-    };
+fn evaluate_lines(input: &str, precedence: Precedence) -> isize {
+    let env = HashMap::new();
 
     input
         .lines()
         .filter_map(|l| {
             let trimmed = l.trim();
 
-            if trimmed.len() > 0 {
+            if !trimmed.is_empty() {
                 Some(trimmed)
             } else {
                 None
             }
         })
         .map(|line| {
-            let stripped: String = line.chars().filter(|c| !c.is_whitespace()).collect();
-
-            expr(&stripped)
-                .expect("Should be able to parse all expressions")
-                .1
+            precedence
+                .parse(line)
+                .map_err(|e| e.to_string())
+                .and_then(|expr| expr.evaluate(&env))
+                .expect("Should be able to evaluate all expressions")
         })
         .sum()
 }
 
+pub fn star_one(input: &str) -> isize {
+    evaluate_lines(input, Precedence::Equal)
+}
+
+pub fn star_two(input: &str) -> isize {
+    evaluate_lines(input, Precedence::AdditionFirst)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::convert::TryFrom;
+
     use super::*;
 
     #[test]
@@ -201,4 +361,153 @@ mod tests {
             23340
         );
     }
+
+    #[test]
+    fn test_display() {
+        let expr = Precedence::Equal.parse("1 + 2 * 3 + (4 * 5)").unwrap();
+        assert_eq!(expr.to_string(), "(((1 + 2) * 3) + (4 * 5))");
+
+        let expr = Precedence::AdditionFirst
+            .parse("1 + 2 * 3 + (4 * 5)")
+            .unwrap();
+        assert_eq!(expr.to_string(), "((1 + 2) * (3 + (4 * 5)))");
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut env = HashMap::new();
+        env.insert("x".to_string(), 4);
+        env.insert("y".to_string(), 5);
+
+        let expr = Precedence::AdditionFirst.parse("2 * 3 + (x * y)").unwrap();
+        assert_eq!(expr.evaluate(&env), Ok(46));
+
+        let expr = Precedence::Equal.parse("2 * z").unwrap();
+        assert_eq!(expr.evaluate(&env), Err("Unbound variable `z`".to_string()));
+
+        let expr = Precedence::Equal
+            .parse("1 + 9223372036854775807 * 2")
+            .unwrap();
+        assert_eq!(
+            expr.evaluate(&env),
+            Err("Overflow evaluating `(1 + 9223372036854775807)`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_left_recursive_grammar() {
+        let inputs = [
+            "2 * 3 + (4 * 5)",
+            "5 + (8 * 3 + 9 + 3 * 4 * 3)",
+            "((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2",
+            "1 + (2 * 3) + (4 * (5 + 6))",
+            " x*y+ 2 ",
+            "7",
+        ];
+
+        for &precedence in Precedence::ALL.iter() {
+            for &input in inputs.iter() {
+                assert_eq!(
+                    precedence.parse_left_recursive(input),
+                    precedence.parse(input),
+                    "{} with {} precedence",
+                    input,
+                    precedence
+                );
+            }
+
+            assert!(precedence.parse_left_recursive("1 + ").is_err());
+            assert!(precedence.parse_left_recursive("(1 + 2").is_err());
+            assert_eq!(
+                precedence
+                    .parse_left_recursive("1 + 2 )")
+                    .unwrap_err()
+                    .offset,
+                6
+            );
+        }
+    }
+
+    #[test]
+    fn test_more_operators() {
+        // New operators only need a description and an entry in a table
+        const SUBTRACT: BinaryOp = BinaryOp {
+            symbol: "-",
+            apply: isize::checked_sub,
+        };
+        const POWER: BinaryOp = BinaryOp {
+            symbol: "^",
+            apply: |a, b| u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+        };
+        const NEGATE: UnaryOp = UnaryOp {
+            symbol: "-",
+            apply: isize::checked_neg,
+        };
+
+        let table = OperatorTable::new()
+            .binary(ADD.symbol, 1, Associativity::Left, |a, b| {
+                Expr::binary(ADD, a, b)
+            })
+            .binary(SUBTRACT.symbol, 1, Associativity::Left, |a, b| {
+                Expr::binary(SUBTRACT, a, b)
+            })
+            .binary(MULTIPLY.symbol, 2, Associativity::Left, |a, b| {
+                Expr::binary(MULTIPLY, a, b)
+            })
+            .binary(POWER.symbol, 4, Associativity::Right, |a, b| {
+                Expr::binary(POWER, a, b)
+            })
+            .unary(NEGATE.symbol, 3, |a| Expr::unary(NEGATE, a));
+        let parse = |input| expression(&table, atom)(input).unwrap().1;
+
+        let expr = parse("-2 ^ 3 ^ 2 - 4 * -x");
+        assert_eq!(expr.to_string(), "((-(2 ^ (3 ^ 2))) - (4 * (-x)))");
+
+        let mut env = HashMap::new();
+        env.insert("x".to_string(), 5);
+        assert_eq!(expr.evaluate(&env), Ok(-492));
+        assert_eq!(
+            parse("2 ^ 64").evaluate(&env),
+            Err("Overflow evaluating `(2 ^ 64)`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input| Precedence::Equal.parse(input).unwrap_err().offset;
+
+        assert_eq!(error("1 + "), 4);
+        assert_eq!(error("1 + * 2"), 4);
+        assert_eq!(error("1 + 2 )"), 6);
+        assert_eq!(error("(1 + 2"), 6);
+        assert_eq!(error("(1 + (2 * ?) + 3)"), 10);
+        assert_eq!(
+            Precedence::Equal.parse("(1 + 2").unwrap_err().message,
+            "Cannot match `)` in empty string"
+        );
+
+        // Multibyte characters are errors rather than being split in the middle
+        assert_eq!(error("1 + é"), 4);
+        assert_eq!(error("1 + 2 é"), 6);
+        assert_eq!(error("ü"), 0);
+        assert_eq!(error("(x + ∑)"), 5);
+
+        let e = Precedence::Equal.parse("1 * ∑ + 2").unwrap_err();
+        assert_eq!((e.offset, e.column("1 * ∑ + 2")), (4, 4));
+
+        let e = ParseError {
+            offset: 7,
+            message: String::new(),
+        };
+        assert_eq!(e.column("é + ü 1"), 5);
+    }
+
+    #[test]
+    fn test_parse_errors_keep_callers_trace() {
+        start_tracing("1 + ?");
+        assert!(Precedence::Equal.parse("1 + ?").is_err());
+        let trace = finish_tracing().expect("The caller's trace is kept");
+
+        assert!(trace.furthest_failure().is_some());
+    }
 }
@@ -0,0 +1,429 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Index;
+use std::str::FromStr;
+
+use crate::math::Vector2;
+
+/// The eight cells surrounding a cell on a 2D grid.
+pub const MOORE: [Vector2<isize>; 8] = [
+    Vector2::new(-1, 0),  // Left
+    Vector2::new(-1, 1),  // Left-Up
+    Vector2::new(0, 1),   // Up
+    Vector2::new(1, 1),   // Right-Up
+    Vector2::new(1, 0),   // Right
+    Vector2::new(1, -1),  // Right-Down
+    Vector2::new(0, -1),  // Down
+    Vector2::new(-1, -1), // Left-Down
+];
+
+/// What lies past the edges of a [`Grid`] along one axis.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Boundary<T> {
+    /// Nothing, the grid ends at the edge.
+    Bounded,
+    /// The opposite edge, as on a torus.
+    Wrap,
+    /// The cell on the edge, repeated forever.
+    Repeat,
+    /// This cell, forever.
+    Infinite(T),
+}
+
+impl<T> Boundary<T> {
+    /// Where `c` lands on an axis of `size` cells: a coordinate on the grid, a cell past the
+    /// edge, or nothing.
+    fn resolve(&self, c: isize, size: usize) -> Option<Result<usize, &T>> {
+        if c >= 0 && (c as usize) < size {
+            return Some(Ok(c as usize));
+        }
+
+        match self {
+            Boundary::Infinite(cell) => Some(Err(cell)),
+            _ if size == 0 => None,
+            Boundary::Bounded => None,
+            Boundary::Wrap => Some(Ok(c.rem_euclid(size as isize) as usize)),
+            Boundary::Repeat => Some(Ok(c.clamp(0, size as isize - 1) as usize)),
+        }
+    }
+}
+
+/// A rectangular 2D grid stored as a single row-major `Vec`.
+///
+/// Locations are `(x, y)` with `(0, 0)` the first cell of the first row, so y grows downwards
+/// the way puzzle inputs are written. Lookups past the edges follow the [`Boundary`] of each
+/// axis, bounded unless set with [`Grid::with_boundaries`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Grid<T> {
+    pub(crate) cells: Vec<T>,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    x_boundary: Boundary<T>,
+    y_boundary: Boundary<T>,
+}
+
+impl<T> Grid<T> {
+    /// **Note:** Fails if the rows aren't all the same length.
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Self, String> {
+        let width = rows.first().map(Vec::len).unwrap_or(0);
+        let height = rows.len();
+
+        if let Some((y, row)) = rows.iter().enumerate().find(|(_, r)| r.len() != width) {
+            return Err(format!(
+                "Row {} has {} cells, expected {}",
+                y,
+                row.len(),
+                width
+            ));
+        }
+
+        Ok(Self {
+            cells: rows.into_iter().flatten().collect(),
+            width,
+            height,
+            x_boundary: Boundary::Bounded,
+            y_boundary: Boundary::Bounded,
+        })
+    }
+
+    /// Set what lies past the left and right edges, `x`, and the top and bottom edges, `y`.
+    pub fn with_boundaries(mut self, x: Boundary<T>, y: Boundary<T>) -> Self {
+        self.x_boundary = x;
+        self.y_boundary = y;
+
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Whether `at` is one of the cells stored in the grid, ignoring the boundaries.
+    pub fn contains(&self, at: Vector2<isize>) -> bool {
+        self.index(at).is_some()
+    }
+
+    pub(crate) fn index(&self, at: Vector2<isize>) -> Option<usize> {
+        if at.x() < 0
+            || at.y() < 0
+            || at.x() as usize >= self.width
+            || at.y() as usize >= self.height
+        {
+            None
+        } else {
+            Some(at.y() as usize * self.width + at.x() as usize)
+        }
+    }
+
+    pub(crate) fn location(&self, idx: usize) -> Vector2<isize> {
+        Vector2::new((idx % self.width) as isize, (idx / self.width) as isize)
+    }
+
+    /// The cell at `at`, or `None` if there's nothing there.
+    ///
+    /// Nothing lies past a bounded edge, even along an infinite axis. When both coordinates are
+    /// past infinite edges the cell of the x axis wins.
+    pub fn get(&self, at: Vector2<isize>) -> Option<&T> {
+        let x = self.x_boundary.resolve(at.x(), self.width)?;
+        let y = self.y_boundary.resolve(at.y(), self.height)?;
+
+        match (x, y) {
+            (Ok(x), Ok(y)) => Some(&self.cells[y * self.width + x]),
+            (Err(cell), _) | (_, Err(cell)) => Some(cell),
+        }
+    }
+
+    /// The cells looking from `at` in the direction `dir`, not including `at` itself.
+    pub fn ray(&self, at: Vector2<isize>, dir: Vector2<isize>) -> impl Iterator<Item = &T> + '_ {
+        self.ray_locations(at, dir)
+            .filter_map(move |location| self.get(location))
+    }
+
+    /// The locations looking from `at` in the direction `dir`, not including `at` itself, as
+    /// they'd be passed to [`Grid::get`].
+    ///
+    /// Stops at a bounded edge and before coming back round to `at`, or to any location already
+    /// passed, e.g. when a repeating edge stops the location from moving. Past an infinite edge
+    /// every cell is the same, so the first location there is the last.
+    pub fn ray_locations(
+        &self,
+        at: Vector2<isize>,
+        dir: Vector2<isize>,
+    ) -> impl Iterator<Item = Vector2<isize>> + '_ {
+        // Only wrapping and repeating edges can bring the ray back to a location it passed
+        let can_repeat =
+            |boundary: &Boundary<T>| matches!(boundary, Boundary::Wrap | Boundary::Repeat);
+        let track = can_repeat(&self.x_boundary) || can_repeat(&self.y_boundary);
+        let mut passed = HashSet::new();
+        let mut past_infinite = false;
+        passed.insert(self.resolve(at).unwrap_or(at));
+
+        (1..).map_while(move |distance| {
+            let location = at + dir * distance;
+            let resolved = self.resolve(location)?;
+            if past_infinite || resolved == at || (track && !passed.insert(resolved)) {
+                return None;
+            }
+            past_infinite = self.index(resolved).is_none();
+
+            Some(location)
+        })
+    }
+
+    /// Where a lookup of `at` ends up in the grid, or `at` itself along an infinite axis.
+    pub(crate) fn resolve(&self, at: Vector2<isize>) -> Option<Vector2<isize>> {
+        let resolve = |boundary: &Boundary<T>, c: isize, size: usize| {
+            boundary
+                .resolve(c, size)
+                .map(|resolved| resolved.map_or(c, |c| c as isize))
+        };
+
+        Some(Vector2::new(
+            resolve(&self.x_boundary, at.x(), self.width)?,
+            resolve(&self.y_boundary, at.y(), self.height)?,
+        ))
+    }
+
+    /// Every cell in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    /// Every cell in row-major order along with its location.
+    pub fn cells(&self) -> impl Iterator<Item = (Vector2<isize>, &T)> {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(idx, cell)| (self.location(idx), cell))
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // `chunks` panics on 0, an empty grid has no rows either way
+        self.cells.chunks(self.width.max(1))
+    }
+
+    /// The cells of column `x` from top to bottom, empty if `x` is outside the grid.
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        let cells = if x < self.width {
+            &self.cells[x..]
+        } else {
+            &[]
+        };
+
+        cells.iter().step_by(self.width.max(1))
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.width).map(move |x| self.column(x))
+    }
+
+    /// The cells surrounding `at`, along with their unwrapped locations, in the order of
+    /// [`MOORE`].
+    pub fn neighbours(&self, at: Vector2<isize>) -> impl Iterator<Item = (Vector2<isize>, &T)> {
+        MOORE
+            .iter()
+            .filter_map(move |&dir| self.get(at + dir).map(|cell| (at + dir, cell)))
+    }
+}
+
+impl<T> Index<Vector2<isize>> for Grid<T> {
+    type Output = T;
+
+    /// **Note:** Panics if there's nothing at `at`, use [`Grid::get`] to check.
+    fn index(&self, at: Vector2<isize>) -> &Self::Output {
+        self.get(at).unwrap_or_else(|| {
+            panic!(
+                "Location ({}, {}) is outside a {}x{} grid",
+                at.x(),
+                at.y(),
+                self.width,
+                self.height
+            )
+        })
+    }
+}
+
+/// Parses a cell per character and a row per non-empty line, ignoring surrounding whitespace.
+impl<T> FromStr for Grid<T>
+where
+    T: TryFrom<char>,
+    T::Error: fmt::Display,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows = s
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .enumerate()
+            .map(|(y, l)| {
+                l.chars()
+                    .enumerate()
+                    .map(|(x, c)| T::try_from(c).map_err(|e| format!("{} at ({}, {})", e, x, y)))
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+
+        Self::from_rows(rows)
+    }
+}
+
+/// Renders a character per cell and a line per row, the inverse of parsing.
+impl<T> fmt::Display for Grid<T>
+where
+    T: Copy,
+    char: From<T>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (y, row) in self.rows().enumerate() {
+            if y > 0 {
+                writeln!(f)?;
+            }
+            for &cell in row {
+                write!(f, "{}", char::from(cell))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+    enum Cell {
+        Open,
+        Wall,
+    }
+
+    impl TryFrom<char> for Cell {
+        type Error = String;
+
+        fn try_from(c: char) -> Result<Self, Self::Error> {
+            match c {
+                '.' => Ok(Cell::Open),
+                '#' => Ok(Cell::Wall),
+                _ => Err(format!("Invalid cell `{}`", c)),
+            }
+        }
+    }
+
+    impl From<Cell> for char {
+        fn from(cell: Cell) -> Self {
+            match cell {
+                Cell::Open => '.',
+                Cell::Wall => '#',
+            }
+        }
+    }
+
+    #[test]
+    fn test_grid() {
+        let grid = Grid::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]).unwrap();
+
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.get(Vector2::new(2, 1)), Some(&6));
+        assert_eq!(grid.get(Vector2::new(3, 0)), None);
+        assert_eq!(grid.get(Vector2::new(0, -1)), None);
+        assert_eq!(grid[Vector2::new(1, 0)], 2);
+        assert_eq!(
+            Grid::from_rows(vec![vec![1, 2], vec![3]]),
+            Err("Row 1 has 1 cells, expected 2".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Location (3, 0) is outside a 3x2 grid")]
+    fn test_index_outside() {
+        let grid = Grid::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]).unwrap();
+
+        let _ = grid[Vector2::new(3, 0)];
+    }
+
+    #[test]
+    fn test_iterators() {
+        let grid = Grid::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]).unwrap();
+
+        assert_eq!(
+            grid.rows().collect::<Vec<_>>(),
+            vec![&[1, 2, 3], &[4, 5, 6]]
+        );
+        assert_eq!(
+            grid.columns()
+                .map(|c| c.copied().collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            vec![vec![1, 4], vec![2, 5], vec![3, 6]]
+        );
+        assert_eq!(grid.column(3).count(), 0);
+        assert_eq!(grid.cells().nth(4), Some((Vector2::new(1, 1), &5)),);
+        assert_eq!(
+            grid.neighbours(Vector2::new(0, 0))
+                .map(|(_, &c)| c)
+                .collect::<Vec<_>>(),
+            vec![4, 5, 2]
+        );
+        assert_eq!(grid.neighbours(Vector2::new(1, 0)).count(), 5);
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let grid = Grid::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]).unwrap();
+        let at = |grid: &Grid<i32>, x, y| grid.get(Vector2::new(x, y)).copied();
+
+        let torus = grid.clone().with_boundaries(Boundary::Wrap, Boundary::Wrap);
+        assert_eq!(at(&torus, 3, 0), Some(1));
+        assert_eq!(at(&torus, -1, -1), Some(6));
+        assert_eq!(at(&torus, 7, 5), Some(5));
+
+        let repeated = grid
+            .clone()
+            .with_boundaries(Boundary::Repeat, Boundary::Bounded);
+        assert_eq!(at(&repeated, -5, 1), Some(4));
+        assert_eq!(at(&repeated, 9, 0), Some(3));
+        assert_eq!(at(&repeated, 0, 2), None);
+
+        let infinite = grid
+            .clone()
+            .with_boundaries(Boundary::Infinite(0), Boundary::Infinite(-1));
+        assert_eq!(at(&infinite, 3, 1), Some(0));
+        assert_eq!(at(&infinite, 1, -4), Some(-1));
+        assert_eq!(at(&infinite, 3, -4), Some(0));
+        assert_eq!(infinite.neighbours(Vector2::new(0, 0)).count(), 8);
+
+        let half_bounded = grid.with_boundaries(Boundary::Infinite(0), Boundary::Bounded);
+        assert_eq!(at(&half_bounded, -1, 0), Some(0));
+        assert_eq!(at(&half_bounded, -1, 2), None);
+        assert!(!half_bounded.contains(Vector2::new(-1, 0)));
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let input = "
+            #..
+            .#.
+        ";
+        let grid = input.parse::<Grid<Cell>>().unwrap();
+
+        assert_eq!(grid[Vector2::new(1, 1)], Cell::Wall);
+        assert_eq!(grid.to_string(), "#..\n.#.");
+        assert_eq!(grid.to_string().parse(), Ok(grid));
+
+        assert_eq!(
+            "#.\n.x".parse::<Grid<Cell>>(),
+            Err("Invalid cell `x` at (1, 1)".to_string())
+        );
+        assert_eq!(
+            "#.\n.".parse::<Grid<Cell>>(),
+            Err("Row 1 has 1 cells, expected 2".to_string())
+        );
+        assert_eq!("".parse::<Grid<Cell>>().map(|g| g.width()), Ok(0));
+    }
+}
@@ -9,10 +9,13 @@ extern crate itertools;
 #[macro_use]
 extern crate lazy_static;
 
+pub mod automaton;
+pub mod console;
+pub mod cycle;
 #[allow(dead_code, unused_imports)]
 mod day01;
 mod day02;
-mod day03;
+pub mod day03;
 mod day04;
 mod day05;
 mod day06;
@@ -20,14 +23,14 @@ mod day07;
 mod day08;
 mod day09;
 mod day10;
-mod day11;
-mod day12;
+pub mod day11;
+pub mod day12;
 mod day13;
 mod day14;
 mod day15;
 mod day16;
-mod day17;
-mod day18;
+pub mod day17;
+pub mod day18;
 mod day19;
 mod day20;
 mod day21;
@@ -35,8 +38,11 @@ mod day22;
 mod day23;
 mod day24;
 mod either;
+pub mod grid;
 pub mod math;
 pub mod parser_combinators;
+pub mod playback;
+pub mod render;
 
 pub use either::Either;
 
@@ -1,3 +1,6 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 
 pub type ParserResult<I, O> = Result<(I, O), String>;
@@ -11,9 +14,8 @@ where
     F: FnMut(&str) -> bool,
 {
     move |input: &str| {
-        for i in 0..input.len() {
-            // This will break with non ASCII
-            if !f(&input[i..i + 1]) {
+        for (i, c) in input.char_indices() {
+            if !f(&input[i..i + c.len_utf8()]) {
                 return Ok((&input[i..], &input[0..i]));
             }
         }
@@ -48,7 +50,9 @@ pub fn match_char(expected: char) -> impl FnMut(&str) -> ParserResult<&str, &str
             .ok_or_else(|| format!("Cannot match `{}` in empty string", expected))
             .and_then(|matched| {
                 if matched {
-                    Ok((&input[1..], &input[0..1]))
+                    let len = expected.len_utf8();
+
+                    Ok((&input[len..], &input[0..len]))
                 } else {
                     Err(format!("Did not match `{}` in `{}`", expected, input))
                 }
@@ -139,6 +143,563 @@ where
     }
 }
 
+/// Memoisation table for packrat parsing.
+///
+/// Together with [`memoize`] and [`left_recursive`] this parses grammars written as rules that
+/// backtrack a lot or are left recursive, e.g.
+/// [`Precedence::parse_left_recursive`](crate::day18::Precedence::parse_left_recursive).
+/// Operator grammars can use [`expression`] instead.
+///
+/// Results are keyed by rule id and offset. Parsers only ever see suffixes of the input being
+/// parsed, so the offset is measured from the end of the input, i.e. it's the length of the
+/// remaining input. This means a table is only valid for a single input and must not be
+/// reused across parses of different inputs.
+pub struct Packrat<O> {
+    table: RefCell<MemoTable<O>>,
+}
+
+type MemoTable<O> = HashMap<(&'static str, usize), Result<(usize, O), String>>;
+
+impl<O: Clone> Packrat<O> {
+    pub fn new() -> Self {
+        Self {
+            table: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn lookup<'a>(&self, rule: &'static str, input: &'a str) -> Option<ParserResult<&'a str, O>> {
+        self.table
+            .borrow()
+            .get(&(rule, input.len()))
+            .map(|result| match result {
+                Ok((remaining, output)) => Ok((&input[input.len() - remaining..], output.clone())),
+                Err(e) => Err(e.clone()),
+            })
+    }
+
+    fn store(&self, rule: &'static str, input: &str, result: &ParserResult<&str, O>) {
+        let entry = match result {
+            Ok((rest, output)) => Ok((rest.len(), output.clone())),
+            Err(e) => Err(e.clone()),
+        };
+
+        self.table.borrow_mut().insert((rule, input.len()), entry);
+    }
+}
+
+impl<O: Clone> Default for Packrat<O> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Memoise the result of `parser` for `rule` in `cache`.
+///
+/// Each rule is evaluated at most once per offset which makes grammars with a lot of
+/// backtracking parse in linear time.
+pub fn memoize<'a, 'c, P, O>(
+    cache: &'c Packrat<O>,
+    rule: &'static str,
+    mut parser: P,
+) -> impl FnMut(&'a str) -> ParserResult<&'a str, O> + 'c
+where
+    P: FnMut(&'a str) -> ParserResult<&'a str, O> + 'c,
+    O: Clone,
+{
+    move |input: &'a str| {
+        if let Some(result) = cache.lookup(rule, input) {
+            return result;
+        }
+
+        let result = parser(input);
+        cache.store(rule, input, &result);
+
+        result
+    }
+}
+
+/// Memoise a directly left recursive `rule`, e.g. `expr <- expr '+' term / term`.
+///
+/// The left recursive call is first seeded with a failure, forcing `parser` to fall back to its
+/// non left recursive alternatives. The result is then fed back into the recursive call for as
+/// long as doing so consumes more input.
+pub fn left_recursive<'a, 'c, P, O>(
+    cache: &'c Packrat<O>,
+    rule: &'static str,
+    mut parser: P,
+) -> impl FnMut(&'a str) -> ParserResult<&'a str, O> + 'c
+where
+    P: FnMut(&'a str) -> ParserResult<&'a str, O> + 'c,
+    O: Clone,
+{
+    move |input: &'a str| {
+        if let Some(result) = cache.lookup(rule, input) {
+            return result;
+        }
+
+        cache.store(
+            rule,
+            input,
+            &Err(format!("Left recursion seed for `{}`", rule)),
+        );
+
+        let mut best = parser(input);
+        cache.store(rule, input, &best);
+
+        while let Ok((best_rest, _)) = &best {
+            match parser(input) {
+                Ok((rest, output)) if rest.len() < best_rest.len() => {
+                    best = Ok((rest, output));
+                    cache.store(rule, input, &best);
+                }
+                _ => break,
+            }
+        }
+
+        best
+    }
+}
+
+thread_local! {
+    static TRACE: RefCell<Option<Trace>> = const { RefCell::new(None) };
+}
+
+/// A rule entry or exit recorded by [`traced`].
+///
+/// Offsets are measured from the start of the input passed to [`start_tracing`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TraceEvent {
+    Enter {
+        rule: &'static str,
+        depth: usize,
+        offset: usize,
+    },
+    Exit {
+        rule: &'static str,
+        depth: usize,
+        offset: usize,
+        result: Result<usize, String>,
+    },
+}
+
+/// The rule entries and exits recorded on the current thread since [`start_tracing`].
+#[derive(Debug, Clone)]
+pub struct Trace {
+    input_len: usize,
+    depth: usize,
+    events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// The offset and error of the failed rule that started furthest into the input. This is
+    /// usually the best guess at where a parse went wrong.
+    pub fn furthest_failure(&self) -> Option<(usize, &str)> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                TraceEvent::Exit {
+                    offset,
+                    result: Err(e),
+                    ..
+                } => Some((*offset, e.as_str())),
+                _ => None,
+            })
+            .max_by_key(|(offset, _)| *offset)
+    }
+
+    fn offset(&self, input: &str) -> usize {
+        self.input_len.saturating_sub(input.len())
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in &self.events {
+            match event {
+                TraceEvent::Enter {
+                    rule,
+                    depth,
+                    offset,
+                } => writeln!(
+                    f,
+                    "{:indent$}> {} @{}",
+                    "",
+                    rule,
+                    offset,
+                    indent = depth * 2
+                )?,
+                TraceEvent::Exit {
+                    rule,
+                    depth,
+                    offset,
+                    result: Ok(end),
+                } => writeln!(
+                    f,
+                    "{:indent$}< {} @{}..{} ok",
+                    "",
+                    rule,
+                    offset,
+                    end,
+                    indent = depth * 2
+                )?,
+                TraceEvent::Exit {
+                    rule,
+                    depth,
+                    offset,
+                    result: Err(e),
+                } => writeln!(
+                    f,
+                    "{:indent$}< {} @{} failed: {}",
+                    "",
+                    rule,
+                    offset,
+                    e,
+                    indent = depth * 2
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Start recording [`traced`] rules on the current thread, discarding any previous trace.
+///
+/// `input` should be the full input about to be parsed, it's used to turn the remaining input
+/// seen by each rule into an offset.
+pub fn start_tracing(input: &str) {
+    TRACE.with(|trace| {
+        *trace.borrow_mut() = Some(Trace {
+            input_len: input.len(),
+            depth: 0,
+            events: vec![],
+        })
+    });
+}
+
+/// Stop recording on the current thread and return the trace, if tracing was started.
+pub fn finish_tracing() -> Option<Trace> {
+    TRACE.with(|trace| trace.borrow_mut().take())
+}
+
+/// Run `f` while recording a fresh trace of `input`, returning its result and the trace.
+///
+/// A trace already being recorded on the current thread is set aside while `f` runs and carries
+/// on afterwards, so this is safe to use inside parsers that are themselves being traced.
+pub fn with_tracing<R>(input: &str, f: impl FnOnce() -> R) -> (R, Trace) {
+    let outer = TRACE.with(|trace| trace.borrow_mut().take());
+
+    start_tracing(input);
+    let result = f();
+    let trace = finish_tracing().expect("Tracing was started");
+
+    TRACE.with(|trace| *trace.borrow_mut() = outer);
+
+    (result, trace)
+}
+
+/// Record entering and exiting `parser` as `name` when tracing is enabled on the current
+/// thread. When it isn't this is a plain pass through to `parser`.
+pub fn traced<'a, P, O>(
+    name: &'static str,
+    mut parser: P,
+) -> impl FnMut(&'a str) -> ParserResult<&'a str, O>
+where
+    P: FnMut(&'a str) -> ParserResult<&'a str, O>,
+{
+    move |input: &'a str| {
+        let entered = TRACE.with(|trace| {
+            trace.borrow_mut().as_mut().map(|trace| {
+                let offset = trace.offset(input);
+                trace.events.push(TraceEvent::Enter {
+                    rule: name,
+                    depth: trace.depth,
+                    offset,
+                });
+                trace.depth += 1;
+
+                offset
+            })
+        });
+
+        let result = parser(input);
+
+        if let Some(offset) = entered {
+            TRACE.with(|trace| {
+                if let Some(trace) = trace.borrow_mut().as_mut() {
+                    trace.depth = trace.depth.saturating_sub(1);
+                    let result = match &result {
+                        Ok((rest, _)) => Ok(trace.offset(rest)),
+                        Err(e) => Err(e.clone()),
+                    };
+                    trace.events.push(TraceEvent::Exit {
+                        rule: name,
+                        depth: trace.depth,
+                        offset,
+                        result,
+                    });
+                }
+            });
+        }
+
+        result
+    }
+}
+
+/// Run `parser` and, if it fails, skip past the end of the current line instead of failing.
+///
+/// The output is the result of `parser` so the caller can decide what to do with failures.
+pub fn recover<'a, P, O>(
+    mut parser: P,
+) -> impl FnMut(&'a str) -> ParserResult<&'a str, Result<O, String>>
+where
+    P: FnMut(&'a str) -> ParserResult<&'a str, O>,
+{
+    move |input: &'a str| match parser(input) {
+        Ok((rest, output)) => Ok((rest, Ok(output))),
+        Err(e) => {
+            let rest = input.find('\n').map(|i| &input[i + 1..]).unwrap_or("");
+
+            Ok((rest, Err(e)))
+        }
+    }
+}
+
+/// A line that could not be parsed by [`parse_all_lines`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    /// 1-based line number in the input.
+    pub line: usize,
+    pub text: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} in `{}`", self.line, self.message, self.text)
+    }
+}
+
+fn skip_blank(input: &str) -> ParserResult<&str, ()> {
+    Ok((input.trim_start_matches([' ', '\t']), ()))
+}
+
+fn end_of_line(input: &str) -> ParserResult<&str, ()> {
+    let (rest, _) = skip_blank(input)?;
+
+    eof(rest).map_err(|_| format!("Unexpected trailing input `{}`", rest))
+}
+
+/// Parse every non empty line of `input` with `parser`, which must consume the entire line.
+///
+/// Unlike [`crate::parse_lines`] this does not stop at the first bad line. Lines that fail to parse
+/// are skipped and reported as diagnostics alongside the items that did parse.
+pub fn parse_all_lines<'a, P, O>(input: &'a str, mut parser: P) -> (Vec<O>, Vec<Diagnostic>)
+where
+    P: FnMut(&'a str) -> ParserResult<&'a str, O>,
+{
+    let mut parse_line = recover(move |input: &'a str| {
+        let (rest, _) = skip_blank(input)?;
+        let (rest, output) = parser(rest)?;
+        let (rest, _) = end_of_line(rest)?;
+
+        Ok((rest, output))
+    });
+
+    let mut items = vec![];
+    let mut diagnostics = vec![];
+
+    for (idx, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // `recover` never fails
+        match parse_line(line.trim_end()).unwrap().1 {
+            Ok(item) => items.push(item),
+            Err(message) => diagnostics.push(Diagnostic {
+                line: idx + 1,
+                text: line.trim().to_string(),
+                message,
+            }),
+        }
+    }
+
+    (items, diagnostics)
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+pub enum Arity<T> {
+    /// A prefix operator, e.g. `-x`.
+    Unary(fn(T) -> T),
+    /// An infix operator, e.g. `x + y`.
+    Binary(Associativity, fn(T, T) -> T),
+}
+
+pub struct Operator<T> {
+    pub symbol: &'static str,
+    /// Operators with higher precedence bind tighter.
+    pub precedence: usize,
+    pub arity: Arity<T>,
+}
+
+/// The operators understood by [`expression`] along with the characters used for grouping.
+pub struct OperatorTable<T> {
+    operators: Vec<Operator<T>>,
+    open: char,
+    close: char,
+}
+
+impl<T> OperatorTable<T> {
+    pub fn new() -> Self {
+        Self {
+            operators: vec![],
+            open: '(',
+            close: ')',
+        }
+    }
+
+    pub fn grouping(mut self, open: char, close: char) -> Self {
+        self.open = open;
+        self.close = close;
+
+        self
+    }
+
+    pub fn unary(mut self, symbol: &'static str, precedence: usize, apply: fn(T) -> T) -> Self {
+        self.operators.push(Operator {
+            symbol,
+            precedence,
+            arity: Arity::Unary(apply),
+        });
+
+        self
+    }
+
+    pub fn binary(
+        mut self,
+        symbol: &'static str,
+        precedence: usize,
+        associativity: Associativity,
+        apply: fn(T, T) -> T,
+    ) -> Self {
+        self.operators.push(Operator {
+            symbol,
+            precedence,
+            arity: Arity::Binary(associativity, apply),
+        });
+
+        self
+    }
+
+    pub fn operators(&self) -> &[Operator<T>] {
+        &self.operators
+    }
+
+    /// Find the longest operator symbol at the start of `input` with the given arity.
+    fn operator_at<'a>(&self, input: &'a str, unary: bool) -> Option<(&'a str, &Operator<T>)> {
+        self.operators
+            .iter()
+            .filter(|op| matches!(op.arity, Arity::Unary(_)) == unary)
+            .filter(|op| input.starts_with(op.symbol))
+            .max_by_key(|op| op.symbol.len())
+            .map(|op| (&input[op.symbol.len()..], op))
+    }
+}
+
+impl<T> Default for OperatorTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn operand<'a, T, A>(
+    table: &OperatorTable<T>,
+    atom: &mut A,
+    input: &'a str,
+) -> ParserResult<&'a str, T>
+where
+    A: FnMut(&'a str) -> ParserResult<&'a str, T>,
+{
+    let (rest, _) = skip_whitespace0(input)?;
+
+    if let Some((rest, op)) = table.operator_at(rest, true) {
+        let (rest, value) = climb(table, atom, rest, op.precedence)?;
+
+        return match op.arity {
+            Arity::Unary(apply) => Ok((rest, apply(value))),
+            Arity::Binary(_, _) => unreachable!(),
+        };
+    }
+
+    if let Ok((rest, _)) = match_char(table.open)(rest) {
+        let (rest, value) = climb(table, atom, rest, 0)?;
+        let (rest, _) = skip_whitespace0(rest)?;
+        let (rest, _) = traced("close", match_char(table.close))(rest)?;
+
+        return Ok((rest, value));
+    }
+
+    traced("atom", &mut *atom)(rest)
+}
+
+fn climb<'a, T, A>(
+    table: &OperatorTable<T>,
+    atom: &mut A,
+    input: &'a str,
+    min_precedence: usize,
+) -> ParserResult<&'a str, T>
+where
+    A: FnMut(&'a str) -> ParserResult<&'a str, T>,
+{
+    let (mut rest, mut lhs) = operand(table, atom, input)?;
+
+    loop {
+        let (after_whitespace, _) = skip_whitespace0(rest)?;
+
+        let (after_op, op) = match table.operator_at(after_whitespace, false) {
+            Some((after_op, op)) if op.precedence >= min_precedence => (after_op, op),
+            _ => break,
+        };
+
+        let (associativity, apply) = match op.arity {
+            Arity::Binary(associativity, apply) => (associativity, apply),
+            Arity::Unary(_) => unreachable!(),
+        };
+        let next_min_precedence = match associativity {
+            Associativity::Left => op.precedence + 1,
+            Associativity::Right => op.precedence,
+        };
+
+        let (new_rest, rhs) = climb(table, atom, after_op, next_min_precedence)?;
+        lhs = apply(lhs, rhs);
+        rest = new_rest;
+    }
+
+    Ok((rest, lhs))
+}
+
+/// Parse an expression of `atom`s combined with the operators in `table` using precedence
+/// climbing. Whitespace between atoms and operators is skipped.
+pub fn expression<'a, 't, T, A>(
+    table: &'t OperatorTable<T>,
+    mut atom: A,
+) -> impl FnMut(&'a str) -> ParserResult<&'a str, T> + 't
+where
+    A: FnMut(&'a str) -> ParserResult<&'a str, T> + 't,
+{
+    move |input: &'a str| climb(table, &mut atom, input, 0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -168,6 +729,8 @@ mod tests {
         let mut match_plus = match_char('+');
 
         assert_eq!(match_plus("+ 923"), Ok((" 923", "+")));
+        assert!(match_plus("é + 923").is_err());
+        assert_eq!(match_char('é')("é + 923"), Ok((" + 923", "é")));
     }
 
     #[test]
@@ -213,6 +776,10 @@ mod tests {
         let mut parser = take_while(|s| s != "(" && s != ")");
 
         assert_eq!(parser("5+8+3)+3)+(3+9*7)"), Ok((")+3)+(3+9*7)", "5+8+3")));
+        assert_eq!(parser("é+ü)+1"), Ok((")+1", "é+ü")));
+
+        let mut letters = take_while(|s| s.chars().all(char::is_alphabetic));
+        assert_eq!(letters("ñandú + 1"), Ok((" + 1", "ñandú")));
     }
 
     #[test]
@@ -281,4 +848,240 @@ mod tests {
         assert_eq!(expr("1+(2*3)+4"), Ok(("", 11)));
         assert_eq!(expr("((2+4*9)*(6+9*8+6)+6)+2+4*2"), Ok(("", 23340)));
     }
+
+    #[test]
+    fn test_memoize() {
+        let cache = Packrat::new();
+        let mut calls = 0;
+
+        {
+            let mut parser = memoize(&cache, "number", |input| {
+                calls += 1;
+                integer::<usize>(input)
+            });
+
+            assert_eq!(parser("12+3"), Ok(("+3", 12)));
+            assert_eq!(parser("12+3"), Ok(("+3", 12)));
+            assert_eq!(parser("+3"), Err("Failed to parse integer ``".to_string()));
+            assert_eq!(parser("+3"), Err("Failed to parse integer ``".to_string()));
+        }
+
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn test_left_recursive() {
+        // difference <- difference '-' number / number
+        fn difference<'a>(cache: &Packrat<isize>, input: &'a str) -> ParserResult<&'a str, isize> {
+            left_recursive(cache, "difference", |input| {
+                one_of_2(
+                    |input| {
+                        let (rest, lhs) = difference(cache, input)?;
+                        let (rest, _) = match_char('-')(rest)?;
+                        let (rest, rhs) = integer::<isize>(rest)?;
+
+                        Ok((rest, lhs - rhs))
+                    },
+                    integer,
+                )(input)
+            })(input)
+        }
+
+        assert_eq!(difference(&Packrat::new(), "10"), Ok(("", 10)));
+        assert_eq!(difference(&Packrat::new(), "10-3-2"), Ok(("", 5)));
+        assert_eq!(difference(&Packrat::new(), "10-3-2)"), Ok((")", 5)));
+        assert_eq!(difference(&Packrat::new(), "10-3-"), Ok(("-", 7)));
+        assert!(difference(&Packrat::new(), "-3").is_err());
+    }
+
+    #[test]
+    fn test_traced() {
+        fn sum(input: &str) -> ParserResult<&str, usize> {
+            traced("sum", |input| {
+                let (rest, lhs) = traced("number", integer::<usize>)(input)?;
+                let (rest, _) = traced("plus", match_char('+'))(rest)?;
+                let (rest, rhs) = traced("number", integer::<usize>)(rest)?;
+
+                Ok((rest, lhs + rhs))
+            })(input)
+        }
+
+        assert_eq!(sum("1+22"), Ok(("", 23)));
+        assert!(finish_tracing().is_none());
+
+        start_tracing("1+22");
+        assert_eq!(sum("1+22"), Ok(("", 23)));
+        let trace = finish_tracing().expect("Tracing was started");
+
+        assert_eq!(
+            trace.to_string(),
+            "> sum @0
+  > number @0
+  < number @0..1 ok
+  > plus @1
+  < plus @1..2 ok
+  > number @2
+  < number @2..4 ok
+< sum @0..4 ok
+"
+        );
+
+        start_tracing("1-2");
+        assert!(sum("1-2").is_err());
+        let trace = finish_tracing().expect("Tracing was started");
+
+        assert_eq!(
+            trace.events()[4],
+            TraceEvent::Exit {
+                rule: "plus",
+                depth: 1,
+                offset: 1,
+                result: Err("Did not match `+` in `-2`".to_string()),
+            }
+        );
+        assert_eq!(
+            trace.to_string().lines().last(),
+            Some("< sum @0 failed: Did not match `+` in `-2`")
+        );
+        assert_eq!(
+            trace.furthest_failure(),
+            Some((1, "Did not match `+` in `-2`"))
+        );
+    }
+
+    #[test]
+    fn test_with_tracing() {
+        let mut number = traced("number", integer::<usize>);
+
+        start_tracing("1 2");
+        assert!(number("1 2").is_ok());
+
+        let (result, inner) = with_tracing("x", || traced("number", integer::<usize>)("x"));
+        assert!(result.is_err());
+        assert_eq!(inner.events().len(), 2);
+        assert_eq!(
+            inner.furthest_failure(),
+            Some((0, "Failed to parse integer ``"))
+        );
+
+        assert!(number("2").is_ok());
+        let outer = finish_tracing().expect("Tracing was restored");
+        assert_eq!(
+            outer.to_string(),
+            "> number @0\n< number @0..1 ok\n> number @2\n< number @2..3 ok\n"
+        );
+
+        let (_, trace) = with_tracing("", || ());
+        assert!(trace.events().is_empty());
+        assert!(finish_tracing().is_none());
+    }
+
+    #[test]
+    fn test_recover() {
+        let mut parser = recover(integer::<usize>);
+
+        assert_eq!(parser("12\n13"), Ok(("\n13", Ok(12))));
+        assert_eq!(
+            parser("a12\n13"),
+            Ok(("13", Err("Failed to parse integer ``".to_string())))
+        );
+        assert_eq!(
+            parser("a12"),
+            Ok(("", Err("Failed to parse integer ``".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_parse_all_lines() {
+        let input = "1 + 2
+  3 + 4
+
+x + 5
+6 + 7 + 8
+9 +
+10 + 11";
+
+        fn sum(input: &str) -> ParserResult<&str, usize> {
+            let (rest, lhs) = integer::<usize>(input)?;
+            let (rest, _) = skip_whitespace0(rest)?;
+            let (rest, _) = match_char('+')(rest)?;
+            let (rest, _) = skip_whitespace0(rest)?;
+            let (rest, rhs) = integer::<usize>(rest)?;
+
+            Ok((rest, lhs + rhs))
+        }
+
+        let (items, diagnostics) = parse_all_lines(input, sum);
+
+        assert_eq!(items, vec![3, 7, 21]);
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    line: 4,
+                    text: "x + 5".to_string(),
+                    message: "Failed to parse integer ``".to_string(),
+                },
+                Diagnostic {
+                    line: 5,
+                    text: "6 + 7 + 8".to_string(),
+                    message: "Unexpected trailing input `+ 8`".to_string(),
+                },
+                Diagnostic {
+                    line: 6,
+                    text: "9 +".to_string(),
+                    message: "Failed to parse integer ``".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "line 4: Failed to parse integer `` in `x + 5`"
+        );
+    }
+
+    #[test]
+    fn test_expression() {
+        let table = OperatorTable::new()
+            .binary("+", 1, Associativity::Left, |a, b| a + b)
+            .binary("-", 1, Associativity::Left, |a, b| a - b)
+            .binary("*", 2, Associativity::Left, |a, b| a * b)
+            .binary("/", 2, Associativity::Left, |a, b| a / b)
+            .binary("^", 4, Associativity::Right, |a: isize, b| a.pow(b as u32))
+            .unary("-", 3, |a| -a);
+        let mut parser = expression(&table, integer::<isize>);
+
+        assert_eq!(parser("1"), Ok(("", 1)));
+        assert_eq!(parser("1 + 2 * 3"), Ok(("", 7)));
+        assert_eq!(parser("(1 + 2) * 3"), Ok(("", 9)));
+        assert_eq!(parser("10 - 3 - 2"), Ok(("", 5)));
+        assert_eq!(parser("8 / 2 / 2"), Ok(("", 2)));
+        assert_eq!(parser("2 ^ 3 ^ 2"), Ok(("", 512)));
+        assert_eq!(parser("-2 ^ 2"), Ok(("", -4)));
+        assert_eq!(parser("-2 * 3"), Ok(("", -6)));
+        assert_eq!(parser("2 * -3"), Ok(("", -6)));
+        assert_eq!(parser("--3"), Ok(("", 3)));
+        assert_eq!(parser("1 + 2 )"), Ok((" )", 3)));
+        assert!(parser("1 + ").is_err());
+        assert!(parser("(1 + 2").is_err());
+
+        start_tracing("(1 + 2");
+        assert!(parser("(1 + 2").is_err());
+        assert_eq!(
+            finish_tracing().unwrap().furthest_failure(),
+            Some((6, "Cannot match `)` in empty string"))
+        );
+    }
+
+    #[test]
+    fn test_expression_longest_operator() {
+        let table = OperatorTable::new()
+            .binary("*", 1, Associativity::Left, |a, b| a * b)
+            .binary("**", 2, Associativity::Right, |a: usize, b| a.pow(b as u32))
+            .grouping('[', ']');
+        let mut parser = expression(&table, integer::<usize>);
+
+        assert_eq!(parser("2 * 3 ** 2"), Ok(("", 18)));
+        assert_eq!(parser("[2 * 3] ** 2"), Ok(("", 36)));
+    }
 }
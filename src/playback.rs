@@ -0,0 +1,226 @@
+use std::io::{self, Write};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// Clear the terminal and move the cursor to the top left.
+pub const CLEAR: &str = "\x1b[2J\x1b[H";
+
+/// Anything that can be shown a frame at a time, such as an automaton or a walker.
+pub trait Simulation {
+    /// The current state as text.
+    fn frame(&self) -> String;
+
+    /// Advance to the next frame. Returns `false`, without changing anything, once there's
+    /// nothing left to show.
+    fn advance(&mut self) -> bool;
+}
+
+/// A key press that controls playback.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Key {
+    TogglePause,
+    /// Show the next frame while paused.
+    Step,
+    Faster,
+    Slower,
+    Quit,
+}
+
+impl Key {
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            ' ' | 'p' => Some(Key::TogglePause),
+            's' | 'n' => Some(Key::Step),
+            '+' => Some(Key::Faster),
+            '-' => Some(Key::Slower),
+            // Ctrl-C, when the terminal doesn't turn it into a signal
+            'q' | '\u{3}' => Some(Key::Quit),
+            _ => None,
+        }
+    }
+}
+
+pub const HELP: &str = "[space] pause  [s] step  [+/-] speed  [q] quit";
+
+/// Write every frame to `out` under a `--- frame N ---` header, stopping after `max_frames`
+/// frames if given. Returns the number of frames written.
+pub fn write_frames(
+    simulation: &mut dyn Simulation,
+    max_frames: Option<usize>,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut frames = 0;
+
+    loop {
+        writeln!(out, "--- frame {} ---", frames)?;
+        writeln!(out, "{}", simulation.frame())?;
+        writeln!(out)?;
+        frames += 1;
+
+        if max_frames.is_some_and(|max| frames >= max) || !simulation.advance() {
+            break Ok(frames);
+        }
+    }
+}
+
+/// Redraw every frame of `simulation` on `out` with ANSI escapes, `frame_rate` frames a second.
+///
+/// Playback is controlled with `keys` and ends when the simulation does, after `max_frames`
+/// frames if given, or on [`Key::Quit`]. Returns the number of frames shown.
+pub fn play(
+    simulation: &mut dyn Simulation,
+    frame_rate: f64,
+    max_frames: Option<usize>,
+    keys: &Receiver<Key>,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut frame_rate = frame_rate.max(0.1);
+    let mut paused = false;
+    let mut frames = 1;
+    let mut finished = false;
+
+    loop {
+        let status = if finished {
+            "finished"
+        } else if paused {
+            "paused"
+        } else {
+            "playing"
+        };
+        write!(
+            out,
+            "{}{}\n\nframe {} ({}, {:.1} fps)  {}\n",
+            CLEAR,
+            simulation.frame(),
+            frames - 1,
+            status,
+            frame_rate,
+            HELP
+        )?;
+        out.flush()?;
+
+        let interval = Duration::from_secs_f64(1.0 / frame_rate);
+        let key = if paused || finished {
+            keys.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            keys.recv_timeout(interval)
+        };
+
+        let advance = match key {
+            Ok(Key::Quit) => break,
+            // Nothing can resume playback once the keys are gone
+            Err(RecvTimeoutError::Disconnected) if paused || finished => break,
+            Ok(Key::TogglePause) => {
+                paused = !paused;
+                false
+            }
+            Ok(Key::Step) => paused,
+            Ok(Key::Faster) => {
+                frame_rate *= 2.0;
+                false
+            }
+            Ok(Key::Slower) => {
+                frame_rate = (frame_rate / 2.0).max(0.1);
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            // Without keys `recv_timeout` returns straight away, so wait for the frame here
+            Err(RecvTimeoutError::Disconnected) => {
+                thread::sleep(interval);
+                true
+            }
+        };
+
+        if advance && !finished {
+            if max_frames.is_some_and(|max| frames >= max) || !simulation.advance() {
+                finished = true;
+            } else {
+                frames += 1;
+            }
+        }
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    use super::*;
+
+    struct Counter {
+        value: usize,
+        end: usize,
+    }
+
+    impl Simulation for Counter {
+        fn frame(&self) -> String {
+            self.value.to_string()
+        }
+
+        fn advance(&mut self) -> bool {
+            if self.value < self.end {
+                self.value += 1;
+                true
+            } else {
+                false
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_frames() {
+        let mut out = vec![];
+        let frames = write_frames(&mut Counter { value: 0, end: 2 }, None, &mut out).unwrap();
+
+        assert_eq!(frames, 3);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "--- frame 0 ---\n0\n\n--- frame 1 ---\n1\n\n--- frame 2 ---\n2\n\n"
+        );
+
+        let mut out = vec![];
+        let frames = write_frames(&mut Counter { value: 0, end: 9 }, Some(2), &mut out).unwrap();
+        assert_eq!(frames, 2);
+    }
+
+    #[test]
+    fn test_play_paused_steps() {
+        let (sender, receiver) = mpsc::channel();
+        for key in [Key::TogglePause, Key::Step, Key::Step, Key::Quit].iter() {
+            sender.send(*key).unwrap();
+        }
+
+        let mut counter = Counter { value: 0, end: 9 };
+        let mut out = vec![];
+        let frames = play(&mut counter, 1.0, None, &receiver, &mut out).unwrap();
+
+        assert_eq!(frames, 3);
+        assert_eq!(counter.value, 2);
+
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.starts_with(CLEAR));
+        assert!(output.contains("2\n\nframe 2 (paused, 1.0 fps)"));
+    }
+
+    #[test]
+    fn test_play_until_finished() {
+        let (sender, receiver) = mpsc::channel();
+        drop(sender);
+
+        let mut counter = Counter { value: 0, end: 3 };
+        let mut out = vec![];
+        let start = Instant::now();
+        let frames = play(&mut counter, 100.0, None, &receiver, &mut out).unwrap();
+
+        // Playback keeps to the frame rate even though no keys can arrive
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert_eq!(frames, 4);
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with(&format!("frame 3 (finished, 100.0 fps)  {}\n", HELP)));
+    }
+}
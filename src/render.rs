@@ -0,0 +1,187 @@
+use std::collections::{BTreeSet, HashSet};
+
+use itertools::Itertools;
+
+use crate::grid::Grid;
+use crate::math::Vector2;
+
+/// The names of the dimensions after x and y, as used by the puzzles.
+const DIMENSION_NAMES: &[&str] = &["z", "w"];
+
+/// The name of the dimension at index `dimension`, where x is 0. Dimensions without a name are
+/// numbered from 1 like the puzzles' dimensions, so the fifth is `d5`.
+fn dimension_name(dimension: usize) -> String {
+    DIMENSION_NAMES
+        .get(dimension - 2)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("d{}", dimension + 1))
+}
+
+/// An inclusive rectangle of 2D locations.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Bounds {
+    pub min: Vector2<isize>,
+    pub max: Vector2<isize>,
+}
+
+impl Bounds {
+    /// The smallest bounds containing every location, if there are any.
+    pub fn around(locations: impl IntoIterator<Item = Vector2<isize>>) -> Option<Self> {
+        locations.into_iter().fold(None, |bounds, l| {
+            Some(match bounds {
+                None => Bounds { min: l, max: l },
+                Some(Bounds { min, max }) => Bounds {
+                    min: Vector2::new(min.x().min(l.x()), min.y().min(l.y())),
+                    max: Vector2::new(max.x().max(l.x()), max.y().max(l.y())),
+                },
+            })
+        })
+    }
+
+    /// Render every location in the bounds, a row per y.
+    pub fn render(&self, cell: impl Fn(Vector2<isize>) -> char) -> String {
+        (self.min.y()..=self.max.y())
+            .map(|y| {
+                (self.min.x()..=self.max.x())
+                    .map(|x| cell(Vector2::new(x, y)))
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+}
+
+/// Render every cell of `grid`, a row per line.
+pub fn render_grid<C: Copy>(grid: &Grid<C>, to_char: impl Fn(C) -> char) -> String {
+    grid.rows()
+        .map(|row| row.iter().map(|&c| to_char(c)).collect::<String>())
+        .join("\n")
+}
+
+/// The header naming a slice through the dimensions after x and y, e.g. `z=0, w=1`.
+pub fn slice_header(slice: &[isize]) -> String {
+    slice
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{}={}", dimension_name(i + 2), c))
+        .join(", ")
+}
+
+/// Render the x/y plane of `live` where the other dimensions are `slice`, as `#` for live and
+/// `.` for dead cells within `bounds`.
+///
+/// **Note:** Panics if `slice` doesn't have a coordinate for every dimension after x and y.
+pub fn render_slice<const N: usize>(
+    live: &HashSet<[isize; N]>,
+    slice: &[isize],
+    bounds: &Bounds,
+) -> String {
+    assert_eq!(
+        slice.len() + 2,
+        N,
+        "Expected a coordinate per extra dimension"
+    );
+
+    let body = bounds.render(|at| {
+        let mut location = [0; N];
+        location[0] = at.x();
+        location[1] = at.y();
+        location[2..].copy_from_slice(slice);
+
+        if live.contains(&location) {
+            '#'
+        } else {
+            '.'
+        }
+    });
+
+    if slice.is_empty() {
+        body
+    } else {
+        format!("{}\n{}", slice_header(slice), body)
+    }
+}
+
+/// Render every slice of `live` with a live cell, the way the puzzles show them.
+///
+/// Slices are ordered by their coordinates and separated by blank lines. All slices share the
+/// bounds of the live cells so they line up.
+pub fn render_slices<const N: usize>(live: &HashSet<[isize; N]>) -> String {
+    let bounds = match Bounds::around(live.iter().map(|l| Vector2::new(l[0], l[1]))) {
+        Some(bounds) => bounds,
+        None => return String::new(),
+    };
+
+    let slices: BTreeSet<&[isize]> = live.iter().map(|l| &l[2..]).collect();
+
+    slices
+        .into_iter()
+        .map(|slice| render_slice(live, slice, &bounds))
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_grid() {
+        let grid = Grid::from_rows(vec![vec![true, false], vec![false, false]]).unwrap();
+
+        assert_eq!(render_grid(&grid, |c| if c { '#' } else { '.' }), "#.\n..");
+    }
+
+    #[test]
+    fn test_render_slices() {
+        let live: HashSet<[isize; 4]> = vec![[0, 1, -1, 0], [2, 2, -1, 0], [1, 3, 0, 2]]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            render_slices(&live),
+            "z=-1, w=0
+#..
+..#
+...
+
+z=0, w=2
+...
+...
+.#."
+        );
+        assert_eq!(
+            render_slice(
+                &live,
+                &[5, 5],
+                &Bounds {
+                    min: Vector2::new(0, 0),
+                    max: Vector2::new(1, 0)
+                }
+            ),
+            "z=5, w=5\n.."
+        );
+        assert_eq!(slice_header(&[1, 2, 3]), "z=1, w=2, d5=3");
+    }
+
+    #[test]
+    fn test_render_5d() {
+        let live: HashSet<[isize; 5]> =
+            vec![[0, 0, 0, 0, 0], [1, 0, 0, 0, 1]].into_iter().collect();
+
+        assert_eq!(
+            render_slices(&live),
+            "z=0, w=0, d5=0
+#.
+
+z=0, w=0, d5=1
+.#"
+        );
+    }
+
+    #[test]
+    fn test_render_2d() {
+        let live: HashSet<[isize; 2]> = vec![[-1, -1], [1, 0]].into_iter().collect();
+
+        assert_eq!(render_slices(&live), "#..\n..#");
+        assert_eq!(render_slices(&HashSet::<[isize; 3]>::new()), "");
+    }
+}